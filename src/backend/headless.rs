@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::egl::{EGLContext, EGLDevice, EGLDisplay};
+use smithay::backend::renderer::damage::OutputDamageTracker;
+use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
+use smithay::backend::renderer::gles::{GlesRenderbuffer, GlesRenderer};
+use smithay::backend::renderer::{Bind, Offscreen};
+use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::calloop::LoopHandle;
+use smithay::utils::Transform;
+
+use crate::config::MAX_HEADLESS_REFRESH_RATE;
+use crate::state::{OutputRenderElements, Twm};
+use crate::LoopData;
+
+/// Renders into offscreen buffers with a software renderer, for running without a display server
+/// or a GPU.
+pub struct Headless {
+    event_loop: LoopHandle<'static, LoopData>,
+    renderer: GlesRenderer,
+    outputs: HashMap<Output, HeadlessOutput>,
+}
+
+struct HeadlessOutput {
+    buffer: GlesRenderbuffer,
+    damage_tracker: OutputDamageTracker,
+}
+
+impl Headless {
+    pub fn seat_name(&self) -> String {
+        "headless".to_owned()
+    }
+
+    pub fn renderer(&mut self) -> &mut GlesRenderer {
+        &mut self.renderer
+    }
+
+    pub fn render(
+        &mut self,
+        twm: &mut Twm,
+        output: &Output,
+        elements: &[OutputRenderElements<
+            GlesRenderer,
+            WaylandSurfaceRenderElement<GlesRenderer>,
+        >],
+    ) {
+        let _span = tracy_client::span!("Headless::render");
+
+        let Some(headless_output) = self.outputs.get_mut(output) else {
+            return;
+        };
+
+        if let Err(err) = self.renderer.bind(headless_output.buffer.clone()) {
+            warn!("error binding the offscreen buffer: {err}");
+            return;
+        }
+        // The buffer is only ever drawn to by us, so it always holds the previous frame.
+        if let Err(err) = headless_output.damage_tracker.render_output(
+            &mut self.renderer,
+            1,
+            elements,
+            twm.config.appearance.clear_color,
+        ) {
+            warn!("error rendering {}: {err:?}", output.name());
+        }
+
+        // There is no vblank to wait for, so pace the clients with a timer instead.
+        twm.output_state.get_mut(output).unwrap().waiting_for_vblank = true;
+
+        let refresh = output.current_mode().unwrap().refresh;
+        let frame_interval = Duration::from_secs_f64(1000. / f64::from(refresh));
+        let output = output.clone();
+        self.event_loop
+            .insert_source(Timer::from_duration(frame_interval), move |_, _, data| {
+                let twm = &mut data.state.twm;
+                if let Some(state) = twm.output_state.get_mut(&output) {
+                    state.waiting_for_vblank = false;
+                    let redraw_pending = std::mem::take(&mut state.redraw_pending);
+                    twm.send_frame_callbacks(&output);
+                    if redraw_pending {
+                        twm.queue_redraw(&output);
+                    }
+                }
+                TimeoutAction::Drop
+            })
+            .unwrap();
+    }
+}
+
+impl Headless {
+    pub fn new(event_loop: LoopHandle<'static, LoopData>) -> anyhow::Result<Self> {
+        let renderer = create_renderer().context("error creating the software renderer")?;
+
+        Ok(Self {
+            event_loop,
+            renderer,
+            outputs: HashMap::new(),
+        })
+    }
+
+    pub fn init(&mut self, twm: &mut Twm) {
+        // In mHz, which the bounds keep well within an i32.
+        let refresh_rate = twm
+            .config
+            .headless
+            .refresh_rate
+            .clamp(1, MAX_HEADLESS_REFRESH_RATE);
+        let refresh = refresh_rate as i32 * 1000;
+
+        for (idx, [width, height]) in twm.config.headless.outputs.clone().into_iter().enumerate() {
+            let output = Output::new(
+                format!("headless-{}", idx + 1),
+                PhysicalProperties {
+                    size: (0, 0).into(),
+                    subpixel: Subpixel::Unknown,
+                    make: "twm".into(),
+                    model: "Headless".into(),
+                },
+            );
+
+            let mode = Mode {
+                size: (width, height).into(),
+                refresh,
+            };
+            output.change_current_state(Some(mode), Some(Transform::Normal), None, None);
+            output.set_preferred(mode);
+
+            let buffer = match self
+                .renderer
+                .create_buffer(Fourcc::Abgr8888, (width, height).into())
+            {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    warn!("error creating a {width}x{height} offscreen buffer: {err}");
+                    continue;
+                }
+            };
+            let damage_tracker = OutputDamageTracker::from_output(&output);
+
+            self.outputs.insert(
+                output.clone(),
+                HeadlessOutput {
+                    buffer,
+                    damage_tracker,
+                },
+            );
+            twm.add_output(output);
+        }
+
+        if self.outputs.is_empty() {
+            warn!("running headless without any outputs");
+        }
+    }
+}
+
+/// Creates a renderer on the software EGL device provided by Mesa (llvmpipe).
+fn create_renderer() -> anyhow::Result<GlesRenderer> {
+    let device = EGLDevice::enumerate()
+        .context("error enumerating EGL devices")?
+        .find(|device| {
+            device
+                .extensions()
+                .iter()
+                .any(|ext| ext == "EGL_MESA_device_software")
+        })
+        .ok_or_else(|| anyhow!("no software EGL device"))?;
+
+    let display = unsafe { EGLDisplay::new(device) }.context("error creating the EGL display")?;
+    let context = EGLContext::new(&display).context("error creating the EGL context")?;
+    let renderer =
+        unsafe { GlesRenderer::new(context) }.context("error creating the GLES renderer")?;
+
+    Ok(renderer)
+}
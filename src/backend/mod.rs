@@ -1,17 +1,29 @@
+use clap::ValueEnum;
+use headless::Headless;
 use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
 use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::output::Output;
 use tty::Tty;
 use winit::Winit;
 
 use crate::state::OutputRenderElements;
 use crate::Twm;
 
+pub mod headless;
 pub mod tty;
 pub mod winit;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    Winit,
+    Tty,
+    Headless,
+}
+
 pub enum Backend {
     Tty(Tty),
     Winit(Winit),
+    Headless(Headless),
 }
 
 impl Backend {
@@ -19,6 +31,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.init(twm),
             Backend::Winit(winit) => winit.init(twm),
+            Backend::Headless(headless) => headless.init(twm),
         }
     }
 
@@ -26,6 +39,7 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.seat_name(),
             Backend::Winit(winit) => winit.seat_name(),
+            Backend::Headless(headless) => headless.seat_name(),
         }
     }
 
@@ -33,20 +47,23 @@ impl Backend {
         match self {
             Backend::Tty(tty) => tty.renderer(),
             Backend::Winit(winit) => winit.renderer(),
+            Backend::Headless(headless) => headless.renderer(),
         }
     }
 
     pub fn render(
         &mut self,
         twm: &mut Twm,
+        output: &Output,
         elements: &[OutputRenderElements<
             GlesRenderer,
             WaylandSurfaceRenderElement<GlesRenderer>,
         >],
     ) {
         match self {
-            Backend::Tty(tty) => tty.render(twm, elements),
-            Backend::Winit(winit) => winit.render(twm, elements),
+            Backend::Tty(tty) => tty.render(twm, output, elements),
+            Backend::Winit(winit) => winit.render(twm, output, elements),
+            Backend::Headless(headless) => headless.render(twm, output, elements),
         }
     }
 
@@ -9,6 +9,7 @@ use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::backend::session::libseat::LibSeatSession;
 use smithay::backend::session::Session;
+use smithay::output::Output;
 use smithay::reexports::calloop::{LoopHandle, RegistrationToken};
 
 use crate::state::OutputRenderElements;
@@ -49,6 +50,7 @@ impl Tty {
     pub fn render(
         &mut self,
         _twm: &mut crate::Twm,
+        _output: &Output,
         _elements: &[OutputRenderElements<
             GlesRenderer,
             WaylandSurfaceRenderElement<GlesRenderer>,
@@ -59,8 +61,8 @@ impl Tty {
 }
 
 impl Tty {
-    pub fn new(_event_loop: LoopHandle<LoopData>) -> Self {
-        todo!()
+    pub fn new(_event_loop: LoopHandle<LoopData>) -> anyhow::Result<Self> {
+        anyhow::bail!("the tty backend is not implemented yet")
     }
 
     pub fn init(&self, _twm: &mut crate::Twm) {
@@ -10,7 +10,7 @@ use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay::reexports::calloop::LoopHandle;
 use smithay::utils::{Rectangle, Transform};
 
-use crate::state::{OutputRenderElements, State, Twm};
+use crate::state::{OutputRenderElements, Twm};
 use crate::LoopData;
 
 pub struct Winit {
@@ -30,7 +30,8 @@ impl Winit {
 
     pub fn render(
         &mut self,
-        _twm: &mut Twm,
+        twm: &mut Twm,
+        output: &Output,
         elements: &[OutputRenderElements<
             GlesRenderer,
             WaylandSurfaceRenderElement<GlesRenderer>,
@@ -42,9 +43,16 @@ impl Winit {
         let damage = Rectangle::from_loc_and_size((0, 0), size);
         self.backend.bind().unwrap();
         self.damage_tracker
-            .render_output(self.backend.renderer(), 0, elements, [0.1, 0.1, 0.1, 1.0])
+            .render_output(
+                self.backend.renderer(),
+                0,
+                elements,
+                twm.config.appearance.clear_color,
+            )
             .unwrap();
         self.backend.submit(Some(&[damage])).unwrap();
+
+        twm.send_frame_callbacks(output);
     }
 }
 
@@ -78,27 +86,24 @@ impl Winit {
         let damage_tracker = OutputDamageTracker::from_output(&output);
 
         let timer = Timer::immediate();
+        let output_ = output.clone();
         event_loop
             .insert_source(timer, move |_, _, data| {
                 winit_event_loop.dispatch_new_events(|event| match event {
                     WinitEvent::Resized { size, .. } => {
-                        data.state
-                            .twm
-                            .output
-                            .as_ref()
-                            .unwrap()
-                            .change_current_state(
-                                Some(Mode {
-                                    size,
-                                    refresh: 60_000,
-                                }),
-                                None,
-                                None,
-                                None,
-                            );
+                        output_.change_current_state(
+                            Some(Mode {
+                                size,
+                                refresh: 60_000,
+                            }),
+                            None,
+                            None,
+                            None,
+                        );
+                        data.state.twm.output_resized(&output_);
                     }
                     WinitEvent::Input(event) => data.state.process_input_event(event),
-                    WinitEvent::Redraw => data.state.twm.queue_redraw(),
+                    WinitEvent::Redraw => data.state.twm.queue_redraw(&output_),
                     WinitEvent::CloseRequested => {
                         data.state.twm.stop_signal.stop();
                     }
@@ -116,9 +121,7 @@ impl Winit {
     }
 
     pub fn init(&mut self, twm: &mut Twm) {
-        let _global = self.output.create_global::<State>(&twm.display_handle);
-        twm.space.map_output(&self.output, (0, 0));
-        twm.output = Some(self.output.clone());
+        twm.add_output(self.output.clone());
         if let Err(err) = self.backend.renderer().bind_wl_display(&twm.display_handle) {
             warn!("error binding renderer wl_display: {err}");
         }
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use smithay::input::keyboard::keysyms::KEY_NoSymbol;
+use smithay::input::keyboard::xkb::{keysym_from_name, KEYSYM_CASE_INSENSITIVE};
+use smithay::input::keyboard::{Keysym, ModifiersState};
+
+use crate::layout::{Axis, ContainerMode, Direction, LayoutKind, LayoutMessage};
+use crate::workspace::WorkspaceReference;
+
+/// The key binding table, keyed on the modifiers and keysym that trigger each binding.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<Bind>")]
+pub struct Binds(HashMap<Key, Bind>);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Bind {
+    pub key: Key,
+    pub action: Action,
+    #[serde(default)]
+    pub trigger: Trigger,
+    /// Whether holding the key down repeats the action.
+    #[serde(default)]
+    pub repeat: bool,
+    /// Whether the binding keeps working while a client inhibits keyboard shortcuts.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+/// A keysym together with the modifiers that must be held, written like `Super+Shift+Q`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Key {
+    pub modifiers: Modifiers,
+    pub keysym: Keysym,
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Modifiers: u8 {
+        const CTRL  = 0b0001;
+        const SHIFT = 0b0010;
+        const ALT   = 0b0100;
+        const SUPER = 0b1000;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    #[default]
+    Press,
+    Release,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    CloseWindow,
+    /// Spawns the terminal from the config.
+    Terminal,
+    /// Spawns a command, program first.
+    Spawn(Vec<String>),
+    ToggleFullscreen,
+    /// Switches the focused window between tiling and floating.
+    ToggleFloating,
+    NextLayout,
+    SetLayout(LayoutKind),
+    /// Forwards a message to the current layout.
+    Layout(LayoutMessage),
+    FocusWorkspace(WorkspaceReference),
+    /// Moves the focused window to a workspace, staying on the current one.
+    MoveToWorkspace(WorkspaceReference),
+    /// Moves the focused window to a workspace and switches to it.
+    MoveToWorkspaceAndFollow(WorkspaceReference),
+    /// Focuses the closest window in a direction, on any shown workspace.
+    FocusWindow(Direction),
+    /// Focuses the window that had the focus before the focused one, going back and forth.
+    FocusLast,
+    /// Focuses the neighbouring output in a direction.
+    FocusOutput(Direction),
+    /// Moves the focused window to the workspace shown on the neighbouring output and follows it.
+    MoveToOutput(Direction),
+}
+
+impl Binds {
+    pub fn find(&self, modifiers: Modifiers, keysym: Keysym) -> Option<&Bind> {
+        self.0.get(&Key { modifiers, keysym })
+    }
+}
+
+impl TryFrom<Vec<Bind>> for Binds {
+    type Error = String;
+
+    fn try_from(binds: Vec<Bind>) -> Result<Self, Self::Error> {
+        let mut map = HashMap::with_capacity(binds.len());
+        for bind in binds {
+            if map.contains_key(&bind.key) {
+                return Err(format!("duplicate binding for {:?}", bind.key));
+            }
+            map.insert(bind.key, bind);
+        }
+        Ok(Self(map))
+    }
+}
+
+impl Default for Binds {
+    fn default() -> Self {
+        let binds = [
+            ("Super+Shift+Q", Action::Quit),
+            ("Super+C", Action::CloseWindow),
+            ("Super+T", Action::Terminal),
+            ("Super+F", Action::ToggleFullscreen),
+            ("Super+Shift+Space", Action::ToggleFloating),
+            ("Super+Space", Action::NextLayout),
+            (
+                "Super+I",
+                Action::Layout(LayoutMessage::ChangeMasterCount(1)),
+            ),
+            (
+                "Super+D",
+                Action::Layout(LayoutMessage::ChangeMasterCount(-1)),
+            ),
+            (
+                "Super+H",
+                Action::Layout(LayoutMessage::ChangeMasterRatio(-0.05)),
+            ),
+            (
+                "Super+L",
+                Action::Layout(LayoutMessage::ChangeMasterRatio(0.05)),
+            ),
+            ("Super+R", Action::Layout(LayoutMessage::CycleColumnWidth)),
+            (
+                "Super+Ctrl+Left",
+                Action::Layout(LayoutMessage::MoveColumn(-1)),
+            ),
+            (
+                "Super+Ctrl+Right",
+                Action::Layout(LayoutMessage::MoveColumn(1)),
+            ),
+            (
+                "Super+Comma",
+                Action::Layout(LayoutMessage::ConsumeIntoColumn),
+            ),
+            (
+                "Super+Period",
+                Action::Layout(LayoutMessage::ExpelFromColumn),
+            ),
+            (
+                "Super+B",
+                Action::Layout(LayoutMessage::Split(ContainerMode::SplitHorizontal)),
+            ),
+            (
+                "Super+V",
+                Action::Layout(LayoutMessage::Split(ContainerMode::SplitVertical)),
+            ),
+            (
+                "Super+W",
+                Action::Layout(LayoutMessage::SetContainerMode(ContainerMode::Tabbed)),
+            ),
+            (
+                "Super+S",
+                Action::Layout(LayoutMessage::SetContainerMode(ContainerMode::Stacked)),
+            ),
+            ("Super+E", Action::Layout(LayoutMessage::ToggleSplit)),
+            (
+                "Super+Shift+Left",
+                Action::Layout(LayoutMessage::MoveWindow(Direction::Left)),
+            ),
+            (
+                "Super+Shift+Right",
+                Action::Layout(LayoutMessage::MoveWindow(Direction::Right)),
+            ),
+            (
+                "Super+Shift+Up",
+                Action::Layout(LayoutMessage::MoveWindow(Direction::Up)),
+            ),
+            (
+                "Super+Shift+Down",
+                Action::Layout(LayoutMessage::MoveWindow(Direction::Down)),
+            ),
+            (
+                "Super+Ctrl+Shift+Left",
+                Action::Layout(LayoutMessage::ChangeWidth(-0.05)),
+            ),
+            (
+                "Super+Ctrl+Shift+Right",
+                Action::Layout(LayoutMessage::ChangeWidth(0.05)),
+            ),
+            (
+                "Super+Ctrl+Shift+Up",
+                Action::Layout(LayoutMessage::ChangeHeight(-0.05)),
+            ),
+            (
+                "Super+Ctrl+Shift+Down",
+                Action::Layout(LayoutMessage::ChangeHeight(0.05)),
+            ),
+            ("Super+Left", Action::FocusWindow(Direction::Left)),
+            ("Super+Right", Action::FocusWindow(Direction::Right)),
+            ("Super+Up", Action::FocusWindow(Direction::Up)),
+            ("Super+Down", Action::FocusWindow(Direction::Down)),
+            ("Super+Tab", Action::FocusLast),
+            (
+                "Super+Ctrl+Alt+Left",
+                Action::Layout(LayoutMessage::Preselect(Direction::Left)),
+            ),
+            (
+                "Super+Ctrl+Alt+Right",
+                Action::Layout(LayoutMessage::Preselect(Direction::Right)),
+            ),
+            (
+                "Super+Ctrl+Alt+Up",
+                Action::Layout(LayoutMessage::Preselect(Direction::Up)),
+            ),
+            (
+                "Super+Ctrl+Alt+Down",
+                Action::Layout(LayoutMessage::Preselect(Direction::Down)),
+            ),
+            ("Super+Shift+R", Action::Layout(LayoutMessage::Rotate(1))),
+            (
+                "Super+Shift+X",
+                Action::Layout(LayoutMessage::Flip(Axis::Horizontal)),
+            ),
+            (
+                "Super+Shift+Y",
+                Action::Layout(LayoutMessage::Flip(Axis::Vertical)),
+            ),
+            ("Super+Alt+Left", Action::FocusOutput(Direction::Left)),
+            ("Super+Alt+Right", Action::FocusOutput(Direction::Right)),
+            ("Super+Alt+Up", Action::FocusOutput(Direction::Up)),
+            ("Super+Alt+Down", Action::FocusOutput(Direction::Down)),
+            (
+                "Super+Alt+Shift+Left",
+                Action::MoveToOutput(Direction::Left),
+            ),
+            (
+                "Super+Alt+Shift+Right",
+                Action::MoveToOutput(Direction::Right),
+            ),
+            ("Super+Alt+Shift+Up", Action::MoveToOutput(Direction::Up)),
+            (
+                "Super+Alt+Shift+Down",
+                Action::MoveToOutput(Direction::Down),
+            ),
+        ];
+
+        let workspace_binds = (1..=9).flat_map(|i| {
+            [
+                (
+                    format!("Super+{i}"),
+                    Action::FocusWorkspace(WorkspaceReference::Index(i)),
+                ),
+                (
+                    format!("Super+Shift+{i}"),
+                    Action::MoveToWorkspace(WorkspaceReference::Index(i)),
+                ),
+                (
+                    format!("Super+Ctrl+{i}"),
+                    Action::MoveToWorkspaceAndFollow(WorkspaceReference::Index(i)),
+                ),
+            ]
+        });
+        let binds = binds
+            .into_iter()
+            .map(|(key, action)| (key.to_owned(), action))
+            .chain(workspace_binds);
+
+        let binds = binds.map(|(key, action)| {
+            let repeat = matches!(
+                action,
+                Action::Layout(
+                    LayoutMessage::ChangeMasterRatio(_)
+                        | LayoutMessage::ChangeWidth(_)
+                        | LayoutMessage::ChangeHeight(_)
+                )
+            );
+            Bind {
+                key: key.parse().unwrap(),
+                action,
+                trigger: Trigger::Press,
+                repeat,
+                locked: false,
+            }
+        });
+
+        Self::try_from(binds.collect::<Vec<_>>()).unwrap()
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('+');
+        // split() always yields at least one item.
+        let name = parts.next_back().unwrap();
+
+        let mut modifiers = Modifiers::empty();
+        for part in parts {
+            modifiers |= match &*part.to_ascii_lowercase() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "mod1" => Modifiers::ALT,
+                "super" | "mod4" | "logo" | "win" => Modifiers::SUPER,
+                _ => return Err(format!("invalid modifier: {part}")),
+            };
+        }
+
+        let keysym = keysym_from_name(name, KEYSYM_CASE_INSENSITIVE);
+        if keysym.raw() == KEY_NoSymbol {
+            return Err(format!("invalid key: {name}"));
+        }
+
+        Ok(Self { modifiers, keysym })
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<&ModifiersState> for Modifiers {
+    fn from(state: &ModifiersState) -> Self {
+        let mut modifiers = Modifiers::empty();
+        modifiers.set(Modifiers::CTRL, state.ctrl);
+        modifiers.set(Modifiers::SHIFT, state.shift);
+        modifiers.set(Modifiers::ALT, state.alt);
+        modifiers.set(Modifiers::SUPER, state.logo);
+        modifiers
+    }
+}
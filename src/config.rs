@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{env, fs};
+
+use anyhow::Context;
+use serde::Deserialize;
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::calloop::LoopHandle;
+
+use crate::binds::Binds;
+use crate::layout::LayoutKind;
+use crate::rules::WindowRule;
+use crate::LoopData;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Highest refresh rate of the headless outputs, in Hz.
+pub const MAX_HEADLESS_REFRESH_RATE: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub input: Input,
+    pub layout: Layout,
+    pub focus: Focus,
+    pub appearance: Appearance,
+    pub decorations: Decorations,
+    pub headless: Headless,
+    /// Command spawned by the terminal binding, program first.
+    pub terminal: Vec<String>,
+    /// Workspace names, in order.
+    pub workspaces: Vec<String>,
+    pub binds: Binds,
+    /// Applied in order, later rules overriding earlier ones.
+    pub window_rules: Vec<WindowRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Input {
+    /// Delay before a held key starts repeating, in milliseconds.
+    pub repeat_delay: i32,
+    /// Repeats per second.
+    pub repeat_rate: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Layout {
+    pub default: LayoutKind,
+    pub gap: i32,
+    pub master_count: usize,
+    pub master_ratio: f64,
+    /// Preset widths of the columns of the scrolling layout, as fractions of the output width.
+    pub column_widths: Vec<f64>,
+    /// Width of new columns of the scrolling layout.
+    pub default_column_width: f64,
+    /// Height of a tab in the tab bars of the manual layout.
+    pub tab_height: i32,
+    /// Share of its area a window keeps when a new one splits it in the bsp layout.
+    pub split_ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Focus {
+    /// Whether windows take the focus when they open, unless a window rule says otherwise.
+    pub new_windows: bool,
+    pub policy: FocusPolicy,
+    /// Whether windows are raised whenever they get the focus, not only when clicked.
+    pub raise: bool,
+    /// Whether the pointer moves to the center of windows focused from the keyboard.
+    pub warp_pointer: bool,
+}
+
+/// How the focus follows the pointer. Clicking a window focuses it whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FocusPolicy {
+    /// Only clicks move the focus.
+    Click,
+    /// Windows take the focus when the pointer enters them, and keep it over empty space.
+    Sloppy,
+    /// The focus is always on the window under the pointer, and nowhere over empty space.
+    Strict,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Appearance {
+    pub clear_color: [f32; 4],
+    /// Color of the square drawn as the pointer when the cursor theme has no cursor.
+    pub pointer_color: [f32; 4],
+}
+
+/// Borders and title bars drawn around tiled windows.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Decorations {
+    /// Width of the borders, 0 to disable them.
+    pub border_width: i32,
+    /// Color of the borders and title bar of the focused window.
+    pub active_color: [f32; 4],
+    pub inactive_color: [f32; 4],
+    /// Color used for windows that asked for attention.
+    pub urgent_color: [f32; 4],
+    /// Whether windows using server-side decorations get a title bar.
+    pub title_bars: bool,
+    pub title_bar_height: i32,
+    /// Pango font description of the titles, like `sans bold 10`.
+    pub title_font: String,
+    pub title_color: [f32; 4],
+}
+
+/// Virtual outputs of the headless backend, only read at startup.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Headless {
+    /// Width and height of every output, placed left to right.
+    pub outputs: Vec<[i32; 2]>,
+    /// Rate at which frame callbacks are sent, in Hz.
+    pub refresh_rate: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            input: Input::default(),
+            layout: Layout::default(),
+            focus: Focus::default(),
+            appearance: Appearance::default(),
+            decorations: Decorations::default(),
+            headless: Headless::default(),
+            terminal: vec!["foot".to_owned()],
+            workspaces: (1..=9).map(|i| i.to_string()).collect(),
+            binds: Binds::default(),
+            window_rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            repeat_delay: 200,
+            repeat_rate: 25,
+        }
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            default: LayoutKind::MasterStack,
+            gap: 6,
+            master_count: 1,
+            master_ratio: 0.5,
+            column_widths: vec![1.0 / 3.0, 0.5, 2.0 / 3.0],
+            default_column_width: 0.5,
+            tab_height: 24,
+            split_ratio: 0.5,
+        }
+    }
+}
+
+impl Default for Focus {
+    fn default() -> Self {
+        Self {
+            new_windows: true,
+            policy: FocusPolicy::Click,
+            raise: false,
+            warp_pointer: false,
+        }
+    }
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.1, 0.1, 0.1, 1.0],
+            pointer_color: [1., 0.8, 0., 1.],
+        }
+    }
+}
+
+impl Default for Decorations {
+    fn default() -> Self {
+        Self {
+            border_width: 2,
+            active_color: [0.3, 0.5, 0.8, 1.],
+            inactive_color: [0.25, 0.25, 0.25, 1.],
+            urgent_color: [0.8, 0.2, 0.2, 1.],
+            title_bars: false,
+            title_bar_height: 20,
+            title_font: "sans 10".to_owned(),
+            title_color: [1., 1., 1., 1.],
+        }
+    }
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self {
+            outputs: vec![[1920, 1080]],
+            refresh_rate: 60,
+        }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/twm/config.toml`, falling back to `~/.config/twm/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("twm").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("error reading {path:?}"))?;
+        let config: Self =
+            toml::from_str(&contents).with_context(|| format!("error parsing {path:?}"))?;
+        anyhow::ensure!(
+            !config.workspaces.is_empty(),
+            "error in {path:?}: workspaces can't be empty"
+        );
+        anyhow::ensure!(
+            (1..=MAX_HEADLESS_REFRESH_RATE).contains(&config.headless.refresh_rate),
+            "error in {path:?}: the headless refresh-rate must be between 1 and \
+             {MAX_HEADLESS_REFRESH_RATE}"
+        );
+        Ok(config)
+    }
+
+    /// Loads the config at `path`, using the defaults if it is missing or invalid.
+    pub fn load_or_default(path: Option<&Path>) -> Self {
+        let Some(path) = path else {
+            return Self::default();
+        };
+
+        if !path.exists() {
+            info!("no config file at {path:?}, using the defaults");
+            return Self::default();
+        }
+
+        match Self::load(path) {
+            Ok(config) => {
+                info!("loaded config from {path:?}");
+                config
+            }
+            Err(err) => {
+                warn!("{err:?}");
+                warn!("using the default config");
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Polls `path` and reloads the config whenever the file changes.
+///
+/// If the new contents fail to parse, the error is logged and the current config stays active.
+pub fn watch(event_loop: &LoopHandle<'static, LoopData>, path: PathBuf) {
+    let mtime = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last_mtime: Option<SystemTime> = mtime(&path);
+
+    event_loop
+        .insert_source(Timer::from_duration(POLL_INTERVAL), move |_, _, data| {
+            let new_mtime = mtime(&path);
+            if new_mtime.is_some() && new_mtime != last_mtime {
+                last_mtime = new_mtime;
+
+                match Config::load(&path) {
+                    Ok(config) => data.state.twm.reload_config(config),
+                    Err(err) => warn!("{err:?}\nkeeping the previous config"),
+                }
+            }
+
+            TimeoutAction::ToDuration(POLL_INTERVAL)
+        })
+        .unwrap();
+}
@@ -0,0 +1,297 @@
+//! The pointer cursor: images from the xcursor theme, surfaces provided by clients, and the cursors
+//! shown while we move or resize windows.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use std::{env, fs, iter};
+
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::element::memory::{
+    MemoryRenderBuffer, MemoryRenderBufferRenderElement,
+};
+use smithay::backend::renderer::element::surface::{
+    render_elements_from_surface_tree, WaylandSurfaceRenderElement,
+};
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::backend::renderer::{ImportAll, ImportMem};
+use smithay::input::pointer::{CursorIcon, CursorImageStatus, CursorImageSurfaceData};
+use smithay::reexports::wayland_server::Resource;
+use smithay::render_elements;
+use smithay::utils::{Logical, Physical, Point, Transform};
+use smithay::wayland::compositor::with_states;
+use xcursor::parser::{parse_xcursor, Image};
+use xcursor::CursorTheme;
+
+/// Logical size of the square drawn when the theme has no cursor to show.
+const FALLBACK_SIZE: i32 = 16;
+
+render_elements! {
+    pub CursorRenderElements<R> where R: ImportAll + ImportMem;
+    Surface = WaylandSurfaceRenderElement<R>,
+    Themed = MemoryRenderBufferRenderElement<R>,
+}
+
+pub struct Cursor {
+    theme: CursorTheme,
+    /// Nominal size of the theme cursors, in logical pixels.
+    size: u32,
+    /// What the client under the pointer asked for.
+    status: CursorImageStatus,
+    /// Shown instead of the client cursor while we move or resize a window.
+    grab_icon: Option<CursorIcon>,
+    fallback_color: [f32; 4],
+    /// Loaded cursors, by icon and buffer scale.
+    cache: HashMap<(CursorIcon, i32), Rc<XCursor>>,
+}
+
+/// The frames of a theme cursor, loaded at one scale.
+struct XCursor {
+    frames: Vec<Frame>,
+    /// Length of one loop of the animation in milliseconds, 0 for static cursors.
+    duration: u32,
+}
+
+struct Frame {
+    buffer: MemoryRenderBuffer,
+    hotspot: Point<f64, Logical>,
+    /// How long the frame is shown in milliseconds.
+    delay: u32,
+}
+
+impl Cursor {
+    /// Loads the theme named by `XCURSOR_THEME` at the size in `XCURSOR_SIZE`, and exports both so
+    /// that clients use the same cursors.
+    pub fn new(fallback_color: [f32; 4]) -> Self {
+        let theme_name = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".to_owned());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(24);
+
+        env::set_var("XCURSOR_THEME", &theme_name);
+        env::set_var("XCURSOR_SIZE", size.to_string());
+
+        Self {
+            theme: CursorTheme::load(&theme_name),
+            size,
+            status: CursorImageStatus::default_named(),
+            grab_icon: None,
+            fallback_color,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self) -> &CursorImageStatus {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: CursorImageStatus) {
+        self.status = status;
+    }
+
+    pub fn set_grab_icon(&mut self, icon: Option<CursorIcon>) {
+        self.grab_icon = icon;
+    }
+
+    pub fn set_fallback_color(&mut self, color: [f32; 4]) {
+        self.fallback_color = color;
+        self.cache.clear();
+    }
+
+    /// Renders the cursor with its hotspot at `location`, in output coordinates.
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        location: Point<f64, Logical>,
+        scale: f64,
+        time: Duration,
+    ) -> Vec<CursorRenderElements<GlesRenderer>> {
+        if self.grab_icon.is_none() {
+            if let CursorImageStatus::Surface(surface) = &self.status {
+                if surface.alive() {
+                    let hotspot = with_states(surface, |states| {
+                        states
+                            .data_map
+                            .get::<CursorImageSurfaceData>()
+                            .unwrap()
+                            .lock()
+                            .unwrap()
+                            .hotspot
+                    });
+                    let location: Point<i32, Physical> =
+                        (location - hotspot.to_f64()).to_physical_precise_round(scale);
+                    return render_elements_from_surface_tree(
+                        renderer,
+                        surface,
+                        location,
+                        scale,
+                        1.,
+                        Kind::Cursor,
+                    );
+                }
+            }
+        }
+
+        let Some(icon) = self.current_icon() else {
+            return Vec::new();
+        };
+        let cursor = self.load(icon, scale.ceil() as i32);
+        let frame = cursor.frame(time);
+
+        let location = (location - frame.hotspot).to_physical(scale);
+        match MemoryRenderBufferRenderElement::from_buffer(
+            renderer,
+            location,
+            &frame.buffer,
+            None,
+            None,
+            None,
+            Kind::Cursor,
+        ) {
+            Ok(element) => vec![CursorRenderElements::Themed(element)],
+            Err(err) => {
+                warn!("error importing the cursor: {err}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// How long until an animated cursor shows its next frame, `None` if the cursor isn't animated.
+    pub fn next_frame_in(&mut self, scale: f64, time: Duration) -> Option<Duration> {
+        let icon = self.current_icon()?;
+        self.load(icon, scale.ceil() as i32).next_frame_in(time)
+    }
+
+    /// The theme cursor to draw, `None` if the cursor is hidden.
+    fn current_icon(&self) -> Option<CursorIcon> {
+        if let Some(icon) = self.grab_icon {
+            return Some(icon);
+        }
+
+        match &self.status {
+            CursorImageStatus::Hidden => None,
+            CursorImageStatus::Named(icon) => Some(*icon),
+            // The surface is drawn as is while alive, this is for after the client destroyed it.
+            CursorImageStatus::Surface(_) => Some(CursorIcon::Default),
+        }
+    }
+
+    fn load(&mut self, icon: CursorIcon, scale: i32) -> Rc<XCursor> {
+        self.cache
+            .entry((icon, scale))
+            .or_insert_with(|| {
+                let names = iter::once(icon.name())
+                    .chain(icon.alt_names().iter().copied())
+                    .chain(["default", "left_ptr"]);
+                let images = names.find_map(|name| load_images(&self.theme, name));
+
+                Rc::new(match images {
+                    Some(images) => XCursor::from_images(images, self.size * scale as u32, scale),
+                    None => {
+                        warn!("no {} cursor in the theme", icon.name());
+                        XCursor::fallback(self.fallback_color, scale)
+                    }
+                })
+            })
+            .clone()
+    }
+}
+
+impl XCursor {
+    /// Picks the frames whose nominal size is the closest to `size`.
+    fn from_images(images: Vec<Image>, size: u32, scale: i32) -> Self {
+        let nominal = images
+            .iter()
+            .map(|image| image.size)
+            .min_by_key(|nominal| nominal.abs_diff(size))
+            .unwrap();
+
+        let frames: Vec<_> = images
+            .into_iter()
+            .filter(|image| image.size == nominal)
+            .map(|image| Frame {
+                buffer: MemoryRenderBuffer::from_slice(
+                    &image.pixels_rgba,
+                    Fourcc::Abgr8888,
+                    (image.width as i32, image.height as i32),
+                    scale,
+                    Transform::Normal,
+                    None,
+                ),
+                hotspot: Point::from((image.xhot as f64, image.yhot as f64))
+                    .downscale(f64::from(scale)),
+                delay: image.delay,
+            })
+            .collect();
+
+        let duration = if frames.len() > 1 {
+            frames.iter().map(|frame| frame.delay).sum()
+        } else {
+            0
+        };
+
+        Self { frames, duration }
+    }
+
+    /// A plain square, so that the pointer stays visible without a cursor theme.
+    fn fallback(color: [f32; 4], scale: i32) -> Self {
+        let size = FALLBACK_SIZE * scale;
+        let pixel = color.map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+        let pixels = pixel.repeat((size * size) as usize);
+
+        let frame = Frame {
+            buffer: MemoryRenderBuffer::from_slice(
+                &pixels,
+                Fourcc::Abgr8888,
+                (size, size),
+                scale,
+                Transform::Normal,
+                None,
+            ),
+            hotspot: Point::from((0., 0.)),
+            delay: 0,
+        };
+
+        Self {
+            frames: vec![frame],
+            duration: 0,
+        }
+    }
+
+    fn frame(&self, time: Duration) -> &Frame {
+        self.frame_at(time).0
+    }
+
+    fn next_frame_in(&self, time: Duration) -> Option<Duration> {
+        if self.duration == 0 {
+            return None;
+        }
+        let remaining = self.frame_at(time).1;
+        Some(Duration::from_millis(u64::from(remaining.max(1))))
+    }
+
+    /// The frame shown at `time` and how many milliseconds it has left.
+    fn frame_at(&self, time: Duration) -> (&Frame, u32) {
+        if self.duration == 0 {
+            return (&self.frames[0], 0);
+        }
+
+        let mut millis = (time.as_millis() % u128::from(self.duration)) as u32;
+        for frame in &self.frames {
+            if millis < frame.delay {
+                return (frame, frame.delay - millis);
+            }
+            millis -= frame.delay;
+        }
+
+        (self.frames.last().unwrap(), 0)
+    }
+}
+
+fn load_images(theme: &CursorTheme, name: &str) -> Option<Vec<Image>> {
+    let path = theme.load_icon(name)?;
+    let data = fs::read(path).ok()?;
+    parse_xcursor(&data).filter(|images| !images.is_empty())
+}
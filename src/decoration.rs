@@ -0,0 +1,403 @@
+//! Borders, title bars and tab bars drawn by the compositor around tiled windows.
+
+use anyhow::Context;
+use pangocairo::cairo::{self, ImageSurface};
+use pangocairo::pango::{self, EllipsizeMode, FontDescription};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::element::memory::{
+    MemoryRenderBuffer, MemoryRenderBufferRenderElement,
+};
+use smithay::backend::renderer::element::solid::{SolidColorBuffer, SolidColorRenderElement};
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::backend::renderer::ImportMem;
+use smithay::desktop::Window;
+use smithay::render_elements;
+use smithay::utils::{Logical, Point, Rectangle, Size, Transform};
+
+use crate::config;
+use crate::window::WindowExt;
+
+/// Horizontal space between the title and the edges of the title bar.
+const TITLE_PADDING: i32 = 6;
+
+render_elements! {
+    pub DecorationRenderElements<R> where R: ImportMem;
+    Border = SolidColorRenderElement,
+    TitleBar = MemoryRenderBufferRenderElement<R>,
+}
+
+/// A tab in a tab bar: the title of its window and the color it is drawn in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tab {
+    pub title: String,
+    pub color: [f32; 4],
+}
+
+/// The tab bars drawn on one output, kept between frames like [`Decoration`].
+#[derive(Default)]
+pub struct TabBars(Vec<Option<RenderedTabBar>>);
+
+/// A rendered tab bar along with everything it was rendered from.
+struct RenderedTabBar {
+    tabs: Vec<Tab>,
+    size: Size<i32, Logical>,
+    stacked: bool,
+    scale: i32,
+    config: config::Decorations,
+    buffer: MemoryRenderBuffer,
+}
+
+/// The decoration of one window, kept between frames so that unchanged parts don't get damaged.
+#[derive(Default)]
+pub struct Decoration {
+    /// Top, bottom, left and right.
+    borders: [SolidColorBuffer; 4],
+    title_bar: Option<TitleBar>,
+}
+
+/// A rendered title bar along with everything it was rendered from.
+struct TitleBar {
+    title: String,
+    width: i32,
+    scale: i32,
+    background: [f32; 4],
+    config: config::Decorations,
+    buffer: MemoryRenderBuffer,
+}
+
+impl Decoration {
+    /// Renders the decoration around a window occupying `geometry`, in output coordinates.
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        window: &Window,
+        geometry: Rectangle<i32, Logical>,
+        color: [f32; 4],
+        scale: f64,
+        config: &config::Decorations,
+    ) -> Vec<DecorationRenderElements<GlesRenderer>> {
+        let mut elements = Vec::new();
+        let border = config.border_width;
+        let title_height = title_bar_height(window, config);
+
+        if title_height > 0 {
+            let location = (geometry.loc.x, geometry.loc.y - title_height);
+            let title = window.title().unwrap_or_default();
+            let buffer_scale = scale.ceil() as i32;
+
+            match self.title_bar_buffer(&title, geometry.size.w, buffer_scale, color, config) {
+                Ok(buffer) => {
+                    let location = Point::<i32, Logical>::from(location)
+                        .to_physical_precise_round::<_, i32>(scale);
+                    match MemoryRenderBufferRenderElement::from_buffer(
+                        renderer,
+                        location.to_f64(),
+                        buffer,
+                        None,
+                        None,
+                        None,
+                        Kind::Unspecified,
+                    ) {
+                        Ok(element) => elements.push(DecorationRenderElements::TitleBar(element)),
+                        Err(err) => warn!("error importing a title bar: {err}"),
+                    }
+                }
+                Err(err) => warn!("error rendering a title bar: {err:?}"),
+            }
+        } else {
+            self.title_bar = None;
+        }
+
+        if border > 0 {
+            let top = geometry.loc.y - title_height - border;
+            let outer_w = geometry.size.w + 2 * border;
+            let inner_h = geometry.size.h + title_height;
+            let rects = [
+                ((geometry.loc.x - border, top), (outer_w, border)),
+                (
+                    (geometry.loc.x - border, geometry.loc.y + geometry.size.h),
+                    (outer_w, border),
+                ),
+                ((geometry.loc.x - border, top + border), (border, inner_h)),
+                (
+                    (geometry.loc.x + geometry.size.w, top + border),
+                    (border, inner_h),
+                ),
+            ];
+
+            for (buffer, (loc, size)) in self.borders.iter_mut().zip(rects) {
+                let rect = Rectangle::<i32, Logical>::from_loc_and_size(loc, size);
+                buffer.update(rect.size, color);
+                elements.push(DecorationRenderElements::Border(
+                    SolidColorRenderElement::from_buffer(
+                        buffer,
+                        rect.loc.to_physical_precise_round(scale),
+                        scale,
+                        1.,
+                        Kind::Unspecified,
+                    ),
+                ));
+            }
+        }
+
+        elements
+    }
+
+    /// Returns the title bar buffer, rendering it again if anything it depends on changed.
+    fn title_bar_buffer(
+        &mut self,
+        title: &str,
+        width: i32,
+        scale: i32,
+        background: [f32; 4],
+        config: &config::Decorations,
+    ) -> anyhow::Result<&MemoryRenderBuffer> {
+        let up_to_date = self.title_bar.as_ref().is_some_and(|bar| {
+            bar.title == title
+                && bar.width == width
+                && bar.scale == scale
+                && bar.background == background
+                && bar.config == *config
+        });
+
+        if !up_to_date {
+            let buffer = render_title_bar(title, width, scale, background, config)?;
+            self.title_bar = Some(TitleBar {
+                title: title.to_owned(),
+                width,
+                scale,
+                background,
+                config: config.clone(),
+                buffer,
+            });
+        }
+
+        Ok(&self.title_bar.as_ref().unwrap().buffer)
+    }
+}
+
+impl TabBars {
+    /// Renders tab bars at the given geometries in output coordinates, with their tabs side by side
+    /// or, for stacked ones, on top of each other.
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        bars: Vec<(Rectangle<i32, Logical>, Vec<Tab>, bool)>,
+        scale: f64,
+        config: &config::Decorations,
+    ) -> Vec<MemoryRenderBufferRenderElement<GlesRenderer>> {
+        let buffer_scale = scale.ceil() as i32;
+        self.0.resize_with(bars.len(), || None);
+
+        let mut elements = Vec::new();
+        for ((geometry, tabs, stacked), cached) in bars.into_iter().zip(&mut self.0) {
+            let up_to_date = cached.as_ref().is_some_and(|bar| {
+                bar.tabs == tabs
+                    && bar.size == geometry.size
+                    && bar.stacked == stacked
+                    && bar.scale == buffer_scale
+                    && bar.config == *config
+            });
+
+            if !up_to_date {
+                *cached = match render_tabs(&tabs, geometry.size, stacked, buffer_scale, config) {
+                    Ok(buffer) => Some(RenderedTabBar {
+                        tabs,
+                        size: geometry.size,
+                        stacked,
+                        scale: buffer_scale,
+                        config: config.clone(),
+                        buffer,
+                    }),
+                    Err(err) => {
+                        warn!("error rendering a tab bar: {err:?}");
+                        None
+                    }
+                };
+            }
+            let Some(bar) = cached else {
+                continue;
+            };
+
+            let location = geometry.loc.to_physical_precise_round::<_, i32>(scale);
+            match MemoryRenderBufferRenderElement::from_buffer(
+                renderer,
+                location.to_f64(),
+                &bar.buffer,
+                None,
+                None,
+                None,
+                Kind::Unspecified,
+            ) {
+                Ok(element) => elements.push(element),
+                Err(err) => warn!("error importing a tab bar: {err}"),
+            }
+        }
+
+        elements
+    }
+}
+
+/// Height of the title bar drawn above `window`, zero if it doesn't get one.
+///
+/// Only windows that agreed to server-side decorations get a title bar, the others draw their own.
+/// Windows under a tab have their title shown there already.
+pub fn title_bar_height(window: &Window, config: &config::Decorations) -> i32 {
+    if config.title_bars && window.is_server_decorated() && !window.is_tabbed() {
+        config.title_bar_height.max(0)
+    } else {
+        0
+    }
+}
+
+/// How much the borders and title bar add to the size of `window`.
+pub fn frame_size(window: &Window, config: &config::Decorations) -> Size<i32, Logical> {
+    let border = config.border_width.max(0);
+    Size::from((2 * border, 2 * border + title_bar_height(window, config)))
+}
+
+/// The area `window` covers at `geometry` along with its borders and title bar.
+pub fn frame_geometry(
+    window: &Window,
+    geometry: Rectangle<i32, Logical>,
+    config: &config::Decorations,
+) -> Rectangle<i32, Logical> {
+    let border = config.border_width.max(0);
+    let title_height = title_bar_height(window, config);
+    Rectangle::from_loc_and_size(
+        (
+            geometry.loc.x - border,
+            geometry.loc.y - border - title_height,
+        ),
+        geometry.size + frame_size(window, config),
+    )
+}
+
+/// The part of `tile` left to `window` once its borders and title bar are taken out.
+pub fn window_area(
+    window: &Window,
+    tile: Rectangle<i32, Logical>,
+    config: &config::Decorations,
+) -> Rectangle<i32, Logical> {
+    let border = config.border_width.max(0);
+    let title_height = title_bar_height(window, config);
+
+    Rectangle::from_loc_and_size(
+        (tile.loc.x + border, tile.loc.y + border + title_height),
+        (
+            (tile.size.w - 2 * border).max(1),
+            (tile.size.h - 2 * border - title_height).max(1),
+        ),
+    )
+}
+
+/// The color of the decoration of a window.
+pub fn color(config: &config::Decorations, focused: bool, urgent: bool) -> [f32; 4] {
+    if urgent {
+        config.urgent_color
+    } else if focused {
+        config.active_color
+    } else {
+        config.inactive_color
+    }
+}
+
+/// The color of a tab. Tabs in the background are drawn darker than the one shown.
+pub fn tab_color(
+    config: &config::Decorations,
+    focused: bool,
+    active: bool,
+    urgent: bool,
+) -> [f32; 4] {
+    let color = color(config, focused, urgent);
+    if active || urgent {
+        color
+    } else {
+        let [r, g, b, a] = color;
+        [r * 0.6, g * 0.6, b * 0.6, a]
+    }
+}
+
+fn render_title_bar(
+    title: &str,
+    width: i32,
+    scale: i32,
+    background: [f32; 4],
+    config: &config::Decorations,
+) -> anyhow::Result<MemoryRenderBuffer> {
+    let height = config.title_bar_height;
+    let tab = Tab {
+        title: title.to_owned(),
+        color: background,
+    };
+    render_tabs(&[tab], Size::from((width, height)), false, scale, config)
+}
+
+/// Draws `tabs` next to each other, or on top of each other if `stacked`, each with its title on
+/// its color. A title bar is a single tab.
+fn render_tabs(
+    tabs: &[Tab],
+    size: Size<i32, Logical>,
+    stacked: bool,
+    scale: i32,
+    config: &config::Decorations,
+) -> anyhow::Result<MemoryRenderBuffer> {
+    let (width, height) = (size.w.max(1), size.h.max(1));
+    let mut surface = ImageSurface::create(cairo::Format::ARgb32, width * scale, height * scale)
+        .context("error creating the cairo surface")?;
+
+    {
+        let cr = cairo::Context::new(&surface).context("error creating the cairo context")?;
+        cr.scale(f64::from(scale), f64::from(scale));
+
+        let count = tabs.len().max(1) as i32;
+        for (idx, tab) in (0..).zip(tabs) {
+            // Tabs split the bar evenly, the last one taking the rounding leftovers.
+            let (x, y, w, h) = if stacked {
+                let h = height / count;
+                let y = idx * h;
+                (0, y, width, if idx == count - 1 { height - y } else { h })
+            } else {
+                let w = width / count;
+                let x = idx * w;
+                (x, 0, if idx == count - 1 { width - x } else { w }, height)
+            };
+
+            let [r, g, b, a] = tab.color.map(f64::from);
+            cr.set_source_rgba(r, g, b, a);
+            cr.rectangle(f64::from(x), f64::from(y), f64::from(w), f64::from(h));
+            cr.fill()?;
+
+            let layout = pangocairo::functions::create_layout(&cr);
+            layout.set_font_description(Some(&FontDescription::from_string(&config.title_font)));
+            layout.set_width((w - 2 * TITLE_PADDING).max(0) * pango::SCALE);
+            layout.set_ellipsize(EllipsizeMode::End);
+            layout.set_text(&tab.title);
+
+            let (_, text_height) = layout.pixel_size();
+            cr.move_to(
+                f64::from(x + TITLE_PADDING),
+                f64::from(y + (h - text_height) / 2),
+            );
+            let [r, g, b, a] = config.title_color.map(f64::from);
+            cr.set_source_rgba(r, g, b, a);
+            pangocairo::functions::show_layout(&cr, &layout);
+        }
+    }
+
+    surface.flush();
+    let data = surface
+        .data()
+        .context("error accessing the cairo surface data")?;
+
+    // Cairo's ARGB32 is native-endian, which is what DRM calls ARGB8888 on little-endian machines.
+    Ok(MemoryRenderBuffer::from_slice(
+        &data,
+        Fourcc::Argb8888,
+        (width * scale, height * scale),
+        scale,
+        Transform::Normal,
+        None,
+    ))
+}
@@ -3,3 +3,6 @@ pub use move_grab::MoveSurfaceGrab;
 
 pub mod resize_grab;
 pub use resize_grab::ResizeSurfaceGrab;
+
+pub mod tile_resize_grab;
+pub use tile_resize_grab::TileResizeGrab;
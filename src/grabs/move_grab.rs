@@ -151,5 +151,9 @@ impl PointerGrab<State> for MoveSurfaceGrab {
         &self.start_data
     }
 
-    fn unset(&mut self, _data: &mut State) {}
+    fn unset(&mut self, data: &mut State) {
+        data.twm.save_floating_geometry(&self.window);
+        data.twm.cursor.set_grab_icon(None);
+        data.twm.queue_redraw_all();
+    }
 }
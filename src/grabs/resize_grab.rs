@@ -2,10 +2,10 @@ use std::cell::RefCell;
 
 use smithay::desktop::{Space, Window};
 use smithay::input::pointer::{
-    AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
-    GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
-    GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
-    PointerInnerHandle, RelativeMotionEvent,
+    AxisFrame, ButtonEvent, CursorIcon, GestureHoldBeginEvent, GestureHoldEndEvent,
+    GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+    GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData,
+    MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
 };
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
@@ -38,6 +38,23 @@ impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
     }
 }
 
+impl ResizeEdge {
+    /// The cursor shown while resizing from these edges.
+    pub fn cursor_icon(self) -> CursorIcon {
+        match self {
+            ResizeEdge::TOP => CursorIcon::NResize,
+            ResizeEdge::BOTTOM => CursorIcon::SResize,
+            ResizeEdge::LEFT => CursorIcon::WResize,
+            ResizeEdge::RIGHT => CursorIcon::EResize,
+            ResizeEdge::TOP_LEFT => CursorIcon::NwResize,
+            ResizeEdge::TOP_RIGHT => CursorIcon::NeResize,
+            ResizeEdge::BOTTOM_LEFT => CursorIcon::SwResize,
+            ResizeEdge::BOTTOM_RIGHT => CursorIcon::SeResize,
+            _ => CursorIcon::Move,
+        }
+    }
+}
+
 pub struct ResizeSurfaceGrab {
     start_data: PointerGrabStartData<State>,
     window: Window,
@@ -265,7 +282,10 @@ impl PointerGrab<State> for ResizeSurfaceGrab {
         &self.start_data
     }
 
-    fn unset(&mut self, _data: &mut State) {}
+    fn unset(&mut self, data: &mut State) {
+        data.twm.cursor.set_grab_icon(None);
+        data.twm.queue_redraw_all();
+    }
 }
 
 /// State of the resize operation.
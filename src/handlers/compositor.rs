@@ -1,4 +1,5 @@
-use smithay::backend::renderer::utils::on_commit_buffer_handler;
+use smithay::backend::renderer::utils::{on_commit_buffer_handler, with_renderer_surface_state};
+use smithay::desktop::Window;
 use smithay::reexports::wayland_server::protocol::wl_buffer;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Client;
@@ -11,7 +12,9 @@ use smithay::{delegate_compositor, delegate_shm};
 
 use super::xdg_shell;
 use crate::grabs::resize_grab;
+use crate::ipc::Event;
 use crate::state::ClientState;
+use crate::window::{MapState, WindowExt};
 use crate::State;
 
 impl CompositorHandler for State {
@@ -29,25 +32,99 @@ impl CompositorHandler for State {
             .message("client commit", 0);
 
         on_commit_buffer_handler::<Self>(surface);
+
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
         if !is_sync_subsurface(surface) {
-            let mut root = surface.clone();
-            while let Some(parent) = get_parent(&root) {
-                root = parent;
-            }
-            if let Some(window) = self
-                .twm
-                .space
-                .elements()
-                .find(|w| w.toplevel().unwrap().wl_surface() == &root)
-            {
+            if let Some(window) = self.twm.find_window(&root) {
                 window.on_commit();
             }
         };
 
-        xdg_shell::handle_commit(&mut self.twm.popups, &self.twm.space, surface);
+        // Clients tend to set their title right before a commit, so look for changes here. Some
+        // set it only after they opened, which can make other window rules match.
+        if let Some(window) = self.twm.find_window(surface).cloned() {
+            if window.title_changed() {
+                self.twm.send_ipc_event(Event::WindowTitleChanged {
+                    id: window.id().get(),
+                    title: window.title(),
+                });
+                if window.is_initial_configure_sent() {
+                    self.apply_window_rules(&window, false);
+                }
+            }
+        }
+
+        if let Some(window) = self.twm.find_window(surface).cloned() {
+            self.toplevel_commit(&window);
+        }
+
+        xdg_shell::handle_commit(&mut self.twm.popups, surface);
         resize_grab::handle_commit(&mut self.twm.space, surface);
 
-        self.twm.queue_redraw()
+        // Floating windows get placed once they have a size, then keep the one the client picks
+        // unless it is fullscreen or maximized.
+        if let Some(window) = self.twm.find_window(surface).cloned() {
+            if window.is_floating() && window.map_state().is_laid_out() {
+                if window.floating_geometry().is_none() {
+                    self.twm.queue_refresh_geometry();
+                } else if !window.is_fullscreen() && !window.is_maximized() {
+                    self.twm.save_floating_geometry(&window);
+                }
+            }
+        }
+
+        if let Some(output) = self.layer_shell_commit(&root) {
+            self.twm.queue_redraw(&output);
+            return;
+        }
+
+        // Only redraw the outputs showing the window, which are none if its workspace is hidden.
+        // Anything else, like popups, redraws everything.
+        match self.twm.find_window(&root).cloned() {
+            Some(window) => {
+                for output in self.twm.space.outputs_for_element(&window) {
+                    self.twm.queue_redraw(&output);
+                }
+            }
+            None => self.twm.queue_redraw_all(),
+        }
+    }
+}
+
+impl State {
+    /// Moves `window` along its life cycle depending on whether the commit attached a buffer.
+    fn toplevel_commit(&mut self, window: &Window) {
+        let surface = window.toplevel().unwrap().wl_surface().clone();
+        let has_buffer = with_renderer_surface_state(&surface, |state| state.buffer().is_some())
+            .unwrap_or(false);
+
+        match window.map_state() {
+            MapState::New | MapState::Unmapped => {
+                if !window.is_initial_configure_sent() {
+                    self.initial_commit(window);
+                }
+            }
+            MapState::Configured if has_buffer => window.set_map_state(MapState::Mapped),
+            MapState::Mapped if !has_buffer => {
+                debug!("window {} unmapped", window.id().get());
+                window.set_map_state(MapState::Unmapped);
+
+                // The window focused before takes over, the unmapped one can't take keys anymore.
+                for ws in &mut self.twm.workspaces {
+                    ws.unfocus(window);
+                }
+                let keyboard = self.twm.seat.get_keyboard().unwrap();
+                if keyboard.current_focus().as_ref() == Some(&surface) {
+                    self.focus_active_window();
+                }
+
+                self.twm.queue_refresh_geometry();
+            }
+            _ => (),
+        }
     }
 }
 
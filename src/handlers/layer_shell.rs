@@ -0,0 +1,131 @@
+use smithay::delegate_layer_shell;
+use smithay::desktop::{layer_map_for_output, LayerSurface, PopupKind, WindowSurfaceType};
+use smithay::output::Output;
+use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::SERIAL_COUNTER;
+use smithay::wayland::compositor::with_states;
+use smithay::wayland::shell::wlr_layer::{
+    KeyboardInteractivity, Layer, LayerSurface as WlrLayerSurface, LayerSurfaceData,
+    WlrLayerShellHandler, WlrLayerShellState,
+};
+use smithay::wayland::shell::xdg::PopupSurface;
+
+use crate::State;
+
+impl WlrLayerShellHandler for State {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.twm.layer_shell_state
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: WlrLayerSurface,
+        wl_output: Option<WlOutput>,
+        _layer: Layer,
+        namespace: String,
+    ) {
+        // Surfaces that don't pick an output go on the active one.
+        let output = wl_output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.twm.active_output.clone());
+        let Some(output) = output else {
+            warn!("closing layer surface {namespace:?}, there is no output to put it on");
+            surface.send_close();
+            return;
+        };
+
+        let mut map = layer_map_for_output(&output);
+        if let Err(err) = map.map_layer(&LayerSurface::new(surface, namespace)) {
+            warn!("error mapping a layer surface: {err}");
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        let Some(output) = self.twm.output_for_layer(surface.wl_surface()) else {
+            return;
+        };
+
+        {
+            let mut map = layer_map_for_output(&output);
+            let layer = map
+                .layer_for_surface(surface.wl_surface(), WindowSurfaceType::TOPLEVEL)
+                .cloned();
+            if let Some(layer) = layer {
+                map.unmap_layer(&layer);
+            }
+        }
+
+        // Give the focus back to the windows if the surface had it.
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        if keyboard.current_focus().as_ref() == Some(surface.wl_surface()) {
+            self.focus_active_window();
+        }
+
+        self.twm.queue_refresh_geometry();
+    }
+
+    fn new_popup(&mut self, _parent: WlrLayerSurface, popup: PopupSurface) {
+        // The popup was created without a parent, so it couldn't be unconstrained until now.
+        self.unconstrain_popup(&popup);
+        let _ = self.twm.popups.track_popup(PopupKind::Xdg(popup));
+    }
+}
+
+delegate_layer_shell!(State);
+
+impl State {
+    /// Handles a commit of a layer surface, returning the output it is on if `surface` is one.
+    ///
+    /// Arranging the layers can change the exclusive zones, in which case the windows are
+    /// rearranged around them.
+    pub(super) fn layer_shell_commit(&mut self, surface: &WlSurface) -> Option<Output> {
+        let output = self.twm.output_for_layer(surface)?;
+
+        let initial_configure_sent = with_states(surface, |states| {
+            states
+                .data_map
+                .get::<LayerSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+
+        let (usable_area_changed, layer) = {
+            let mut map = layer_map_for_output(&output);
+            let old_zone = map.non_exclusive_zone();
+            map.arrange();
+
+            let layer = map
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .unwrap()
+                .clone();
+            if !initial_configure_sent {
+                layer.layer_surface().send_configure();
+            }
+
+            (map.non_exclusive_zone() != old_zone, layer)
+        };
+
+        if usable_area_changed {
+            self.twm.queue_refresh_geometry();
+        }
+
+        // Exclusive surfaces on the upper layers take the keyboard as soon as they are mapped, like
+        // lock screens and launchers expect, and give it back once they stop asking for it.
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        let focused = keyboard.current_focus().as_ref() == Some(surface);
+        let exclusive = layer.cached_state().keyboard_interactivity
+            == KeyboardInteractivity::Exclusive
+            && matches!(layer.layer(), Layer::Top | Layer::Overlay);
+        if initial_configure_sent && exclusive && !focused {
+            keyboard.set_focus(self, Some(surface.clone()), SERIAL_COUNTER.next_serial());
+        } else if focused && !layer.can_receive_keyboard_focus() {
+            self.focus_active_window();
+        }
+
+        Some(output)
+    }
+}
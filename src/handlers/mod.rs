@@ -1,19 +1,38 @@
 mod compositor;
+mod layer_shell;
 mod xdg_shell;
 
 //
 // Wl Seat
+use smithay::desktop::Window;
+use smithay::input::pointer::CursorImageStatus;
 use smithay::input::{Seat, SeatHandler, SeatState};
+use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Resource;
+use smithay::wayland::keyboard_shortcuts_inhibit::{
+    KeyboardShortcutsInhibitHandler, KeyboardShortcutsInhibitState, KeyboardShortcutsInhibitor,
+};
 use smithay::wayland::output::OutputHandler;
 use smithay::wayland::selection::data_device::{
     set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState,
     ServerDndGrabHandler,
 };
 use smithay::wayland::selection::SelectionHandler;
-use smithay::{delegate_data_device, delegate_output, delegate_seat};
+use smithay::wayland::tablet_manager::TabletSeatHandler;
+use smithay::wayland::shell::xdg::decoration::{XdgDecorationHandler, XdgDecorationState};
+use smithay::wayland::shell::xdg::ToplevelSurface;
+use smithay::wayland::xdg_activation::{
+    XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+};
+use smithay::{
+    delegate_cursor_shape, delegate_data_device, delegate_keyboard_shortcuts_inhibit,
+    delegate_output, delegate_seat, delegate_xdg_activation, delegate_xdg_decoration,
+};
 
+use crate::ipc::Event;
+use crate::state::Twm;
+use crate::window::WindowExt;
 use crate::State;
 
 impl SeatHandler for State {
@@ -25,22 +44,68 @@ impl SeatHandler for State {
         &mut self.twm.seat_state
     }
 
-    fn cursor_image(
-        &mut self,
-        _seat: &Seat<Self>,
-        _image: smithay::input::pointer::CursorImageStatus,
-    ) {
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
+        self.twm.cursor.set_status(image);
+
+        let location = self.twm.seat.get_pointer().unwrap().current_location();
+        self.twm.queue_redraw_under(location);
     }
 
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
         let dh = &self.twm.display_handle;
         let client = focused.and_then(|s| dh.get_client(s.id()).ok());
         set_data_device_focus(dh, seat, client);
+
+        let window = focused
+            .and_then(|surface| self.twm.find_window(surface))
+            .cloned();
+        self.twm.send_ipc_event(Event::FocusChanged {
+            id: window.as_ref().map(|window| window.id().get()),
+        });
+
+        // Border colors follow the focus.
+        self.twm.queue_redraw_all();
+
+        if focused.is_none() {
+            self.twm.active_workspace_mut().set_focus(None);
+            return;
+        }
+
+        // Focusing a window on another output makes that output the active one.
+        let Some(window) = window else {
+            return;
+        };
+        let Some(idx) = self
+            .twm
+            .workspaces
+            .iter()
+            .position(|ws| ws.contains(&window))
+        else {
+            return;
+        };
+        if let Some(output) = self.twm.output_for_workspace(idx).cloned() {
+            self.twm.active_output = Some(output);
+        }
+        window.set_urgent(false);
+        self.twm.workspaces[idx].set_focus(Some(window));
+
+        // Some layouts arrange the windows around the focused one.
+        self.twm.queue_refresh_geometry();
     }
 }
 
 delegate_seat!(State);
 
+//
+// Cursor Shape
+//
+
+// Shapes requested by clients reach `cursor_image` as named cursors, drawn from the theme. Tablet
+// tools aren't supported, so their cursors are ignored.
+impl TabletSeatHandler for State {}
+
+delegate_cursor_shape!(State);
+
 //
 // Wl Data Device
 //
@@ -66,3 +131,142 @@ delegate_data_device!(State);
 
 impl OutputHandler for State {}
 delegate_output!(State);
+
+//
+// Keyboard Shortcuts Inhibit
+//
+
+impl KeyboardShortcutsInhibitHandler for State {
+    fn keyboard_shortcuts_inhibit_state(&mut self) -> &mut KeyboardShortcutsInhibitState {
+        &mut self.twm.keyboard_shortcuts_inhibit_state
+    }
+
+    fn new_inhibitor(&mut self, inhibitor: KeyboardShortcutsInhibitor) {
+        // Grant every request, bindings marked as locked keep working regardless.
+        inhibitor.activate();
+        self.twm
+            .keyboard_shortcuts_inhibiting_surfaces
+            .insert(inhibitor.wl_surface().clone(), inhibitor);
+    }
+
+    fn inhibitor_destroyed(&mut self, inhibitor: KeyboardShortcutsInhibitor) {
+        self.twm
+            .keyboard_shortcuts_inhibiting_surfaces
+            .remove(inhibitor.wl_surface());
+    }
+}
+
+delegate_keyboard_shortcuts_inhibit!(State);
+
+//
+// Xdg Decoration
+//
+
+impl XdgDecorationHandler for State {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        self.set_decoration_mode(&toplevel, None);
+    }
+
+    fn request_mode(&mut self, toplevel: ToplevelSurface, mode: DecorationMode) {
+        self.set_decoration_mode(&toplevel, Some(mode));
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        self.set_decoration_mode(&toplevel, None);
+    }
+}
+
+delegate_xdg_decoration!(State);
+
+impl State {
+    /// Answers the decoration mode the client of `toplevel` asked for, `None` if it leaves the
+    /// choice to us.
+    fn set_decoration_mode(
+        &mut self,
+        toplevel: &ToplevelSurface,
+        requested: Option<DecorationMode>,
+    ) {
+        let Some(window) = self.twm.find_window(toplevel.wl_surface()).cloned() else {
+            return;
+        };
+        window.set_requested_decoration_mode(requested);
+        toplevel.with_pending_state(|state| {
+            state.decoration_mode = Some(decoration_mode(&window));
+        });
+
+        // Windows configured already hear about the mode right away, even when the relayout
+        // leaves them out. The others get it with their initial configure.
+        if window.is_initial_configure_sent() {
+            toplevel.send_pending_configure();
+        }
+
+        // The title bar takes room from the window.
+        self.twm.queue_refresh_geometry();
+    }
+}
+
+impl Twm {
+    /// Switches `window` to the decoration mode matching whether it floats, if its client asked
+    /// for one.
+    pub fn update_decoration_mode(&mut self, window: &Window) {
+        if window.requested_decoration_mode().is_none() {
+            return;
+        }
+
+        let mode = decoration_mode(window);
+        let toplevel = window.toplevel().unwrap();
+        if toplevel.with_pending_state(|state| state.decoration_mode.replace(mode)) == Some(mode) {
+            return;
+        }
+
+        if window.is_initial_configure_sent() {
+            toplevel.send_pending_configure();
+        }
+        self.queue_refresh_geometry();
+    }
+}
+
+/// Floating windows get the decorations their client asks for. Tiled ones are decorated by us
+/// whatever their client prefers: client-side title bars and shadows look out of place on them.
+fn decoration_mode(window: &Window) -> DecorationMode {
+    if window.is_floating()
+        && window.requested_decoration_mode() == Some(DecorationMode::ClientSide)
+    {
+        DecorationMode::ClientSide
+    } else {
+        DecorationMode::ServerSide
+    }
+}
+
+//
+// Xdg Activation
+//
+
+impl XdgActivationHandler for State {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.twm.activation_state
+    }
+
+    fn request_activation(
+        &mut self,
+        _token: XdgActivationToken,
+        _token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        // Windows don't get to steal the focus, they are marked as urgent instead.
+        let Some(window) = self.twm.find_window(&surface).cloned() else {
+            return;
+        };
+        let focus = self.twm.seat.get_keyboard().unwrap().current_focus();
+        if focus.as_ref() == Some(&surface) {
+            return;
+        }
+
+        window.set_urgent(true);
+        for output in self.twm.space.outputs_for_element(&window) {
+            self.twm.queue_redraw(&output);
+        }
+    }
+}
+
+delegate_xdg_activation!(State);
@@ -1,10 +1,10 @@
 use smithay::delegate_xdg_shell;
 use smithay::desktop::{
-    find_popup_root_surface, get_popup_toplevel_coords, PopupKind, PopupManager, Space, Window,
+    find_popup_root_surface, get_popup_toplevel_coords, layer_map_for_output, PopupKeyboardGrab,
+    PopupKind, PopupManager, PopupPointerGrab, PopupUngrabStrategy, Window, WindowSurfaceType,
 };
-use smithay::input::pointer::{Focus, GrabStartData as PointerGrabStartData};
+use smithay::input::pointer::{CursorIcon, Focus, GrabStartData as PointerGrabStartData};
 use smithay::input::Seat;
-use smithay::output::Output;
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::protocol::{wl_output, wl_seat};
@@ -14,10 +14,14 @@ use smithay::wayland::compositor::with_states;
 use smithay::wayland::seat::WaylandFocus;
 use smithay::wayland::shell::xdg::{
     PopupSurface, PositionerState, ToplevelSurface, XdgPopupSurfaceData, XdgShellHandler,
-    XdgShellState, XdgToplevelSurfaceData,
+    XdgShellState,
 };
 
-use crate::grabs::{MoveSurfaceGrab, ResizeSurfaceGrab};
+use crate::grabs::resize_grab::ResizeEdge;
+use crate::grabs::{MoveSurfaceGrab, ResizeSurfaceGrab, TileResizeGrab};
+use crate::ipc::Event;
+use crate::rules::ResolvedRules;
+use crate::window::{MapState, WindowExt};
 use crate::State;
 
 impl XdgShellHandler for State {
@@ -26,10 +30,12 @@ impl XdgShellHandler for State {
     }
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
+        // The window is placed, laid out and focused on its initial commit, once its app id,
+        // title, parent and size hints are known.
         let window = Window::new_wayland_window(surface);
-        self.twm.space.map_element(window, (0, 0), false);
-
-        self.twm.refresh_geometry();
+        self.twm
+            .active_workspace_mut()
+            .add_window(window.clone(), false);
     }
 
     fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
@@ -67,6 +73,10 @@ impl XdgShellHandler for State {
                 .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
                 .unwrap()
                 .clone();
+            // Tiled windows go where the layout puts them.
+            if !window.is_floating() {
+                return;
+            }
             let initial_window_location = self.twm.space.element_location(&window).unwrap();
 
             let grab = MoveSurfaceGrab {
@@ -75,6 +85,7 @@ impl XdgShellHandler for State {
                 initial_window_location,
             };
 
+            self.twm.cursor.set_grab_icon(Some(CursorIcon::Grabbing));
             pointer.set_grab(self, grab, serial, Focus::Clear);
         }
     }
@@ -100,6 +111,16 @@ impl XdgShellHandler for State {
                 .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
                 .unwrap()
                 .clone();
+            let edges = ResizeEdge::from(edges);
+
+            // Tiled windows resize along with their neighbours, by moving the splits of the
+            // layout.
+            if !window.is_floating() {
+                let grab = TileResizeGrab::start(start_data, window, edges);
+                self.twm.cursor.set_grab_icon(Some(edges.cursor_icon()));
+                pointer.set_grab(self, grab, serial, Focus::Clear);
+                return;
+            }
             let initial_window_location = self.twm.space.element_location(&window).unwrap();
             let initial_window_size = window.geometry().size;
 
@@ -112,68 +133,74 @@ impl XdgShellHandler for State {
             let grab = ResizeSurfaceGrab::start(
                 start_data,
                 window,
-                edges.into(),
+                edges,
                 Rectangle::from_loc_and_size(initial_window_location, initial_window_size),
             );
 
+            self.twm.cursor.set_grab_icon(Some(edges.cursor_icon()));
             pointer.set_grab(self, grab, serial, Focus::Clear);
         }
     }
 
-    fn grab(&mut self, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {
-        // TODO popup grabs
+    fn grab(&mut self, surface: PopupSurface, seat: wl_seat::WlSeat, serial: Serial) {
+        let seat = Seat::from_resource(&seat).unwrap();
+        let popup = PopupKind::Xdg(surface);
+
+        // Menus can only grab on behalf of a window or a layer surface we know about.
+        let Ok(root) = find_popup_root_surface(&popup) else {
+            return;
+        };
+        if self.twm.find_window(&root).is_none() && self.twm.output_for_layer(&root).is_none() {
+            return;
+        }
+
+        let mut grab = match self.twm.popups.grab_popup(root, popup, &seat, serial) {
+            Ok(grab) => grab,
+            Err(err) => {
+                debug!("refusing a popup grab: {err:?}");
+                return;
+            }
+        };
+
+        // The grab has to come from the input event that opened the popup, which is either the one
+        // behind the current grab or, for nested menus, the one behind the parent popup's grab.
+        let previous_serial = grab.previous_serial().unwrap_or(serial);
+
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial) || keyboard.has_grab(previous_serial))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(self, PopupKeyboardGrab::new(&grab), serial);
+        }
+
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial) || pointer.has_grab(previous_serial))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            // Clicking outside of the popup chain dismisses it.
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
     }
 
     fn fullscreen_request(
         &mut self,
         surface: ToplevelSurface,
-        wl_output: Option<wl_output::WlOutput>,
+        _wl_output: Option<wl_output::WlOutput>,
     ) {
         if surface
             .current_state()
             .capabilities
             .contains(xdg_toplevel::WmCapabilities::Fullscreen)
         {
-            // NOTE: This is only one part of the solution. We can set the
-            // location and configure size here, but the surface should be rendered fullscreen
-            // independently from its buffer size
-            let wl_surface = surface.wl_surface();
-
-            let output = wl_output
-                .as_ref()
-                .and_then(Output::from_resource)
-                .or_else(|| {
-                    let w = self
-                        .twm
-                        .space
-                        .elements()
-                        .find(|window| {
-                            window
-                                .wl_surface()
-                                .map(|s| &*s == wl_surface)
-                                .unwrap_or(false)
-                        })
-                        .cloned();
-                    w.and_then(|w| self.twm.space.outputs_for_element(&w).first().cloned())
-                });
-
-            if let Some(output) = output {
-                let geometry = self.twm.space.output_geometry(&output).unwrap();
-
-                surface.with_pending_state(|state| {
-                    state.states.set(xdg_toplevel::State::Fullscreen);
-                    state.size = Some(geometry.size);
-                });
-
-                let window = self
-                    .twm
-                    .space
-                    .elements()
-                    .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
-                    .unwrap()
-                    .clone();
-                self.twm.space.map_element(window, geometry.loc, true);
-            }
+            // Windows stay on the output of their workspace, whichever one the client asks for.
+            self.fullscreen_window(&surface);
         }
 
         // The protocol demands us to always reply with a configure,
@@ -190,12 +217,17 @@ impl XdgShellHandler for State {
             return;
         }
 
+        // The layout gives the window its size back.
         surface.with_pending_state(|state| {
             state.states.unset(xdg_toplevel::State::Fullscreen);
             state.size = None;
         });
+        self.twm.queue_refresh_geometry();
 
-        surface.send_pending_configure();
+        self.send_window_event(surface.wl_surface(), |id| Event::WindowFullscreenChanged {
+            id,
+            fullscreen: false,
+        });
     }
 
     fn maximize_request(&mut self, surface: ToplevelSurface) {
@@ -210,19 +242,27 @@ impl XdgShellHandler for State {
                 .space
                 .elements()
                 .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
-                .unwrap()
-                .clone();
-            let geometry = self
-                .twm
-                .space
-                .output_geometry(self.twm.output.as_ref().unwrap())
-                .unwrap();
+                .cloned();
+            let output = window
+                .as_ref()
+                .and_then(|w| self.twm.space.outputs_for_element(w).first().cloned())
+                .or_else(|| self.twm.active_output.clone());
 
-            surface.with_pending_state(|state| {
-                state.states.set(xdg_toplevel::State::Maximized);
-                state.size = Some(geometry.size);
-            });
-            self.twm.space.map_element(window, geometry.loc, true);
+            // Maximized windows stay clear of panels and other exclusive zones.
+            if let Some(geometry) = output.map(|o| self.twm.usable_area(&o)) {
+                surface.with_pending_state(|state| {
+                    state.states.set(xdg_toplevel::State::Maximized);
+                    state.size = Some(geometry.size);
+                });
+                if let Some(window) = window {
+                    self.twm.space.map_element(window, geometry.loc, true);
+                }
+
+                self.send_window_event(wl_surface, |id| Event::WindowMaximizedChanged {
+                    id,
+                    maximized: true,
+                });
+            }
         }
 
         surface.send_configure();
@@ -241,15 +281,37 @@ impl XdgShellHandler for State {
             state.states.unset(xdg_toplevel::State::Maximized);
             state.size = None;
         });
-        surface.send_pending_configure();
+        self.twm.queue_refresh_geometry();
+
+        self.send_window_event(surface.wl_surface(), |id| Event::WindowMaximizedChanged {
+            id,
+            maximized: false,
+        });
     }
 
-    fn toplevel_destroyed(&mut self, _surface: ToplevelSurface) {
-        self.twm.queue_redraw();
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.twm.find_window(surface.wl_surface()).cloned() {
+            self.twm.remove_window(&window);
+            // Windows destroyed before their initial commit were never announced.
+            if window.map_state() != MapState::New {
+                self.twm.send_ipc_event(Event::WindowClosed {
+                    id: window.id().get(),
+                });
+            }
+        }
+
+        // The window focused before takes the focus back.
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        if keyboard.current_focus().as_ref() == Some(surface.wl_surface()) {
+            self.focus_active_window();
+            self.warp_pointer_to_focus();
+        }
+
+        self.twm.queue_refresh_geometry();
     }
 
     fn popup_destroyed(&mut self, _surface: PopupSurface) {
-        self.twm.queue_redraw();
+        self.twm.queue_redraw_all();
     }
 }
 
@@ -280,28 +342,7 @@ fn check_grab(
 }
 
 /// Should be called on `WlSurface::commit`
-pub fn handle_commit(popups: &mut PopupManager, space: &Space<Window>, surface: &WlSurface) {
-    // Handle toplevel commits.
-    if let Some(window) = space
-        .elements()
-        .find(|w| w.toplevel().unwrap().wl_surface() == surface)
-        .cloned()
-    {
-        let initial_configure_sent = with_states(surface, |states| {
-            states
-                .data_map
-                .get::<XdgToplevelSurfaceData>()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .initial_configure_sent
-        });
-
-        if !initial_configure_sent {
-            window.toplevel().unwrap().send_configure();
-        }
-    }
-
+pub fn handle_commit(popups: &mut PopupManager, surface: &WlSurface) {
     // Handle popup commits.
     popups.commit(surface);
     if let Some(popup) = popups.find_popup(surface) {
@@ -328,28 +369,206 @@ pub fn handle_commit(popups: &mut PopupManager, space: &Space<Window>, surface:
 }
 
 impl State {
-    fn unconstrain_popup(&self, popup: &PopupSurface) {
+    /// Places and lays out a window on its initial commit, according to the window rules, or
+    /// where it was before its client unmapped it.
+    pub(super) fn initial_commit(&mut self, window: &Window) {
+        let remap = window.map_state() == MapState::Unmapped;
+        window.set_map_state(MapState::Configured);
+        if remap {
+            // The rules applied when the window first opened. Applying them again would undo
+            // where it was moved since, like its workspace or whether it floats, and take the
+            // focus.
+            self.twm.refresh_geometry();
+        } else {
+            self.apply_window_rules(window, true);
+        }
+        // The rules and size hints decided whether the window floats.
+        self.twm.update_decoration_mode(window);
+
+        // Only now are its app id and title known, and its workspace and focus settled.
+        if !remap {
+            if let Some(info) = self.twm.ipc_window(window) {
+                self.twm.send_ipc_event(Event::WindowOpened(info));
+            }
+        }
+
+        // Floating windows and those on hidden workspaces weren't configured by the layout, but
+        // the initial commit still needs a reply. Floating ones pick their own size.
+        if !window.is_initial_configure_sent() {
+            window.toplevel().unwrap().send_configure();
+        }
+    }
+
+    /// Evaluates the window rules for `window` and applies the settings that changed since they
+    /// were last evaluated, which is all of them on the initial commit.
+    ///
+    /// Rules that start matching later, once the title changed, apply as well, except for taking
+    /// the focus which only happens when the window opens.
+    pub(super) fn apply_window_rules(&mut self, window: &Window, initial: bool) {
+        let old = if initial {
+            ResolvedRules::default()
+        } else {
+            window.rules()
+        };
+        let new = ResolvedRules::new(
+            &self.twm.config.window_rules,
+            window.app_id().as_deref(),
+            window.title().as_deref(),
+        );
+        if !initial && new == old {
+            return;
+        }
+        window.set_rules(new.clone());
+
+        if initial {
+            window.set_floating(new.floating.unwrap_or_else(|| window.prefers_floating()));
+        } else if let Some(floating) = new.floating.filter(|_| new.floating != old.floating) {
+            if floating != window.is_floating() {
+                self.twm.toggle_floating(window);
+            }
+        }
+
+        // Only floating windows keep this size, the layout overrides it for the others.
+        let toplevel = window.toplevel().unwrap();
+        if let Some([width, height]) = new.size.filter(|_| new.size != old.size) {
+            toplevel.with_pending_state(|state| state.size = Some((width, height).into()));
+        }
+
+        if new.workspace != old.workspace || new.output != old.output {
+            let idx = match (&new.workspace, &new.output) {
+                (Some(reference), _) => reference.find(&self.twm.workspaces),
+                (None, Some(name)) => self
+                    .twm
+                    .output_state
+                    .iter()
+                    .find(|(output, _)| output.name() == *name)
+                    .map(|(_, state)| state.workspace),
+                (None, None) => None,
+            };
+            if let Some(idx) = idx {
+                if !self.twm.workspaces[idx].contains(window) {
+                    self.twm.remove_window(window);
+                    self.twm.workspaces[idx].add_window(window.clone(), false);
+                }
+            }
+        }
+
+        let focus = initial && new.focus.unwrap_or(self.twm.config.focus.new_windows);
+        if focus {
+            if let Some(ws) = self
+                .twm
+                .workspaces
+                .iter_mut()
+                .find(|ws| ws.contains(window))
+            {
+                ws.set_focus(Some(window.clone()));
+            }
+        }
+
+        if let Some(fullscreen) = new.fullscreen.filter(|_| new.fullscreen != old.fullscreen) {
+            if fullscreen {
+                self.fullscreen_window(toplevel);
+            } else {
+                self.unfullscreen_request(toplevel.clone());
+            }
+        }
+
+        // The initial configure has to carry the layout, so that one can't wait.
+        if initial {
+            self.twm.refresh_geometry();
+        } else {
+            self.twm.queue_refresh_geometry();
+        }
+
+        if focus && self.twm.active_workspace().contains(window) {
+            self.focus_active_window();
+            self.warp_pointer_to_focus();
+        }
+        self.twm.queue_redraw_all();
+    }
+
+    /// Makes the window of `surface` fullscreen on the output showing its workspace, without
+    /// sending the configure. The relayout maps it there, or once its workspace is shown.
+    fn fullscreen_window(&mut self, surface: &ToplevelSurface) {
+        let wl_surface = surface.wl_surface();
+        let Some(window) = self.twm.find_window(wl_surface).cloned() else {
+            return;
+        };
+        let size = self
+            .twm
+            .workspaces
+            .iter()
+            .position(|ws| ws.contains(&window))
+            .and_then(|idx| self.twm.output_for_workspace(idx))
+            .and_then(|output| self.twm.space.output_geometry(output))
+            .map(|geometry| geometry.size);
+
+        surface.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Fullscreen);
+            if size.is_some() {
+                state.size = size;
+            }
+        });
+        self.twm.queue_refresh_geometry();
+
+        self.send_window_event(wl_surface, |id| Event::WindowFullscreenChanged {
+            id,
+            fullscreen: true,
+        });
+    }
+
+    /// Sends the IPC event built by `event` from the id of the window of `surface`.
+    fn send_window_event(&mut self, surface: &WlSurface, event: impl FnOnce(u64) -> Event) {
+        if let Some(window) = self.twm.find_window(surface) {
+            let event = event(window.id().get());
+            self.twm.send_ipc_event(event);
+        }
+    }
+
+    /// Keeps `popup` on the output its window or layer surface is on.
+    pub(super) fn unconstrain_popup(&self, popup: &PopupSurface) {
         let Ok(root) = find_popup_root_surface(&PopupKind::Xdg(popup.clone())) else {
             return;
         };
-        let Some(window) = self
+
+        // The geometry of the root surface and of its output.
+        let (root_geo, output_geo) = if let Some(window) = self
             .twm
             .space
             .elements()
             .find(|w| w.toplevel().unwrap().wl_surface() == &root)
-        else {
+        {
+            let Some(output) = self
+                .twm
+                .space
+                .outputs_for_element(window)
+                .into_iter()
+                .next()
+            else {
+                return;
+            };
+            (
+                self.twm.space.element_geometry(window).unwrap(),
+                self.twm.space.output_geometry(&output).unwrap(),
+            )
+        } else if let Some(output) = self.twm.output_for_layer(&root) {
+            let output_geo = self.twm.space.output_geometry(&output).unwrap();
+            let map = layer_map_for_output(&output);
+            let layer = map
+                .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                .unwrap();
+            let mut layer_geo = map.layer_geometry(layer).unwrap();
+            layer_geo.loc += output_geo.loc;
+            (layer_geo, output_geo)
+        } else {
             return;
         };
 
-        let output = self.twm.space.outputs().next().unwrap();
-        let output_geo = self.twm.space.output_geometry(output).unwrap();
-        let window_geo = self.twm.space.element_geometry(window).unwrap();
-
         // The target geometry for the positioner should be relative to its parent's geometry, so
         // we will compute that here.
         let mut target = output_geo;
         target.loc -= get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
-        target.loc -= window_geo.loc;
+        target.loc -= root_geo.loc;
 
         popup.with_pending_state(|state| {
             state.geometry = state.positioner.get_unconstrained_geometry(target);
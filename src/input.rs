@@ -1,24 +1,26 @@
-use std::cell::Cell;
+use std::process::Command;
+use std::time::Duration;
 
 use smithay::backend::input::{
     AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
     KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
 };
-use smithay::input::keyboard::{FilterResult, Keysym};
-use smithay::input::pointer::{AxisFrame, ButtonEvent, MotionEvent, RelativeMotionEvent};
-use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
+use smithay::input::keyboard::{FilterResult, Keycode, KeysymHandle, ModifiersState};
+use smithay::input::pointer::{
+    AxisFrame, ButtonEvent, Focus, GrabStartData as PointerGrabStartData, MotionEvent,
+    RelativeMotionEvent,
+};
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
-use smithay::utils::SERIAL_COUNTER;
+use smithay::utils::{Logical, Point, SERIAL_COUNTER};
 use smithay::wayland::shell::xdg::XdgShellHandler;
 
+use crate::binds::{Action, Modifiers, Trigger};
+use crate::config::FocusPolicy;
+use crate::grabs::TileResizeGrab;
 use crate::state::State;
-
-enum KeyAction {
-    Quit,
-    CloseWindow,
-    Terminal,
-    ToggleFullscreen,
-}
+use crate::window::WindowExt;
+use crate::workspace::WorkspaceReference;
 
 impl State {
     pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
@@ -29,100 +31,74 @@ impl State {
             InputEvent::Keyboard { event, .. } => {
                 let serial = SERIAL_COUNTER.next_serial();
                 let time = Event::time_msec(&event);
+                let key_code = event.key_code();
                 let press_state = event.state();
 
-                let action = self.twm.seat.get_keyboard().unwrap().input(
+                let keyboard = self.twm.seat.get_keyboard().unwrap();
+
+                // The filter runs with the keyboard locked, so look up the focus beforehand.
+                let inhibited = keyboard
+                    .current_focus()
+                    .and_then(|surface| {
+                        self.twm
+                            .keyboard_shortcuts_inhibiting_surfaces
+                            .get(&surface)
+                    })
+                    .is_some_and(|inhibitor| inhibitor.is_active());
+
+                let action = keyboard.input(
                     self,
-                    event.key_code(),
+                    key_code,
                     press_state,
                     serial,
                     time,
-                    |_, _, keysym| {
-                        if press_state == KeyState::Pressed {
-                            let sym = keysym.modified_sym();
-                            if sym == Keysym::Q {
-                                FilterResult::Intercept(KeyAction::Quit)
-                            } else if sym == Keysym::C {
-                                FilterResult::Intercept(KeyAction::CloseWindow)
-                            } else if sym == Keysym::F {
-                                FilterResult::Intercept(KeyAction::ToggleFullscreen)
-                            } else if sym == Keysym::T {
-                                FilterResult::Intercept(KeyAction::Terminal)
-                            } else {
-                                FilterResult::Forward
-                            }
-                        } else {
-                            FilterResult::Forward
-                        }
+                    |state, mods, keysym| {
+                        state.filter_key(key_code, press_state, mods, keysym, inhibited)
                     },
                 );
 
-                match action {
-                    Some(KeyAction::Quit) => {
-                        info!("quitting");
-                        self.twm.stop_signal.stop();
-                    }
-                    Some(KeyAction::CloseWindow) => {
-                        if let Some(focus) = self.twm.seat.get_keyboard().unwrap().current_focus() {
-                            for window in self.twm.space.elements() {
-                                let found = Cell::new(false);
-                                window.with_surfaces(|surface, _| {
-                                    if surface == &focus {
-                                        found.set(true);
-                                    }
-                                });
-                                if found.get() {
-                                    window.toplevel().unwrap().send_close();
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    Some(KeyAction::Terminal) => {
-                        std::process::Command::new("foot").spawn().ok();
-                    }
-                    Some(KeyAction::ToggleFullscreen) => {
-                        if let Some(focus) = self.twm.seat.get_keyboard().unwrap().current_focus() {
-                            // FIXME: is there a better way of doing this?
-                            let window = self.twm.space.elements().find(|window| {
-                                let found = Cell::new(false);
-                                window.with_surfaces(|surface, _| {
-                                    if surface == &focus {
-                                        found.set(true);
-                                    }
-                                });
-                                found.get()
-                            });
-                            if let Some(window) = window {
-                                let toplevel = window.toplevel().unwrap().clone();
-                                if toplevel
-                                    .current_state()
-                                    .states
-                                    .contains(xdg_toplevel::State::Fullscreen)
-                                {
-                                    self.unfullscreen_request(toplevel);
-                                } else {
-                                    self.fullscreen_request(toplevel, None);
-                                }
-                            }
-                        }
+                if let Some(action) = action.flatten() {
+                    if let Err(err) = self.do_action(action) {
+                        warn!("{err}");
                     }
-                    None => {}
                 }
             }
             InputEvent::PointerMotion { event, .. } => {
                 let serial = SERIAL_COUNTER.next_serial();
 
                 let pointer = self.twm.seat.get_pointer().unwrap();
-                let mut pointer_location = pointer.current_location();
+                let old_location = pointer.current_location();
+                let mut pointer_location = old_location + event.delta();
 
-                pointer_location += event.delta();
-
-                let output = self.twm.space.outputs().next().unwrap();
-                let output_geo = self.twm.space.output_geometry(output).unwrap();
+                // The pointer crosses freely between outputs, but past the outer edges it stays on
+                // the output it was on.
+                if self
+                    .twm
+                    .space
+                    .output_under(pointer_location)
+                    .next()
+                    .is_none()
+                {
+                    let Some(output_geo) = self
+                        .twm
+                        .space
+                        .output_under(old_location)
+                        .next()
+                        .or_else(|| self.twm.space.outputs().next())
+                        .and_then(|output| self.twm.space.output_geometry(output))
+                    else {
+                        return;
+                    };
 
-                pointer_location.x = pointer_location.x.clamp(0., output_geo.size.w as f64);
-                pointer_location.y = pointer_location.y.clamp(0., output_geo.size.h as f64);
+                    let max_x = output_geo.loc.x + output_geo.size.w - 1;
+                    let max_y = output_geo.loc.y + output_geo.size.h - 1;
+                    pointer_location.x = pointer_location
+                        .x
+                        .clamp(output_geo.loc.x as f64, max_x as f64);
+                    pointer_location.y = pointer_location
+                        .y
+                        .clamp(output_geo.loc.y as f64, max_y as f64);
+                }
 
                 let under = self.twm.surface_under(pointer_location);
                 pointer.motion(
@@ -145,18 +121,29 @@ impl State {
                     },
                 );
 
-                self.twm.queue_redraw();
+                self.focus_follows_pointer(pointer_location);
+
+                self.twm.queue_redraw_under(old_location);
+                self.twm.queue_redraw_under(pointer_location);
             }
             InputEvent::PointerMotionAbsolute { event, .. } => {
-                let output = self.twm.space.outputs().next().unwrap();
-
-                let output_geo = self.twm.space.output_geometry(output).unwrap();
+                // Absolute positions come from the winit window, which is the first output.
+                let Some(output_geo) = self
+                    .twm
+                    .space
+                    .outputs()
+                    .next()
+                    .and_then(|output| self.twm.space.output_geometry(output))
+                else {
+                    return;
+                };
 
                 let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
 
                 let serial = SERIAL_COUNTER.next_serial();
 
                 let pointer = self.twm.seat.get_pointer().unwrap();
+                let old_location = pointer.current_location();
 
                 let under = self.twm.surface_under(pos);
 
@@ -171,7 +158,10 @@ impl State {
                 );
                 pointer.frame(self);
 
-                self.twm.queue_redraw();
+                self.focus_follows_pointer(pos);
+
+                self.twm.queue_redraw_under(old_location);
+                self.twm.queue_redraw_under(pos);
             }
             InputEvent::PointerButton { event, .. } => {
                 let pointer = self.twm.seat.get_pointer().unwrap();
@@ -181,16 +171,45 @@ impl State {
 
                 let button = event.button_code();
 
+                // The button is a button code as defined in the
+                // Linux kernel's linux/input-event-codes.h header file, e.g. BTN_LEFT.
+                const BTN_LEFT: u32 = 0x110;
+
                 let button_state = event.state();
 
                 if ButtonState::Pressed == button_state && !pointer.is_grabbed() {
-                    if let Some((window, _loc)) = self
+                    // Clicking on an output focuses it, even if there is no window under the
+                    // pointer.
+                    if let Some(output) = self
                         .twm
                         .space
-                        .element_under(pointer.current_location())
+                        .output_under(pointer.current_location())
+                        .next()
+                        .cloned()
+                    {
+                        self.twm.active_output = Some(output);
+                    }
+
+                    if self.twm.exclusive_layer_surface().is_some() {
+                        // The layer surface keeps the keyboard wherever the click lands.
+                    } else if let Some((layer, _)) =
+                        self.twm.layer_under(pointer.current_location())
+                    {
+                        if layer.can_receive_keyboard_focus() {
+                            keyboard.set_focus(self, Some(layer.wl_surface().clone()), serial);
+                        }
+                    } else if let Some(window) =
+                        self.twm.tab_under(pointer.current_location()).cloned()
+                    {
+                        // Clicking a tab brings its window to the front.
+                        self.focus_window(&window);
+                    } else if let Some((window, _loc)) = self
+                        .twm
+                        .window_under(pointer.current_location())
                         .map(|(w, l)| (w.clone(), l))
                     {
                         self.twm.space.raise_element(&window, true);
+                        self.twm.raise_floating();
                         keyboard.set_focus(
                             self,
                             Some(window.toplevel().unwrap().wl_surface().clone()),
@@ -199,6 +218,19 @@ impl State {
                         self.twm.space.elements().for_each(|window| {
                             window.toplevel().unwrap().send_pending_configure();
                         });
+                    } else if let Some((window, edges)) = (button == BTN_LEFT)
+                        .then(|| self.twm.tile_edge_under(pointer.current_location()))
+                        .flatten()
+                    {
+                        // Dragging a gap moves the splits of the layout on either side of it.
+                        let start_data = PointerGrabStartData {
+                            focus: None,
+                            button,
+                            location: pointer.current_location(),
+                        };
+                        let grab = TileResizeGrab::start(start_data, window, edges);
+                        self.twm.cursor.set_grab_icon(Some(edges.cursor_icon()));
+                        pointer.set_grab(self, grab, serial, Focus::Clear);
                     } else {
                         self.twm.space.elements().for_each(|window| {
                             window.set_activated(false);
@@ -261,4 +293,219 @@ impl State {
             _ => {}
         }
     }
+
+    /// Decides whether a key event triggers a binding or goes to the focused client.
+    ///
+    /// Keys that triggered a binding are suppressed until released, so that clients never see
+    /// half of a key press. Bindings with a release trigger fire from here on the key release.
+    fn filter_key(
+        &mut self,
+        key_code: Keycode,
+        press_state: KeyState,
+        mods: &ModifiersState,
+        keysym: KeysymHandle<'_>,
+        inhibited: bool,
+    ) -> FilterResult<Option<Action>> {
+        if press_state == KeyState::Released {
+            if matches!(self.twm.key_repeat, Some((code, _)) if code == key_code) {
+                self.stop_key_repeat();
+            }
+
+            return match self.twm.suppressed_keys.remove(&key_code) {
+                Some(action) => FilterResult::Intercept(action),
+                None => FilterResult::Forward,
+            };
+        }
+
+        let Some(sym) = keysym.raw_latin_sym_or_raw_current_sym() else {
+            return FilterResult::Forward;
+        };
+        let Some(bind) = self.twm.config.binds.find(Modifiers::from(mods), sym) else {
+            return FilterResult::Forward;
+        };
+        if inhibited && !bind.locked {
+            return FilterResult::Forward;
+        }
+        let bind = bind.clone();
+
+        match bind.trigger {
+            Trigger::Press => {
+                self.twm.suppressed_keys.insert(key_code, None);
+                if bind.repeat {
+                    self.start_key_repeat(key_code, bind.action.clone());
+                }
+                FilterResult::Intercept(Some(bind.action))
+            }
+            Trigger::Release => {
+                self.twm.suppressed_keys.insert(key_code, Some(bind.action));
+                FilterResult::Intercept(None)
+            }
+        }
+    }
+
+    fn start_key_repeat(&mut self, key_code: Keycode, action: Action) {
+        self.stop_key_repeat();
+
+        let input = &self.twm.config.input;
+        let delay = Duration::from_millis(input.repeat_delay.max(0) as u64);
+        let interval = Duration::from_secs_f64(1. / f64::from(input.repeat_rate.max(1)));
+
+        let token = self
+            .twm
+            .event_loop
+            .insert_source(Timer::from_duration(delay), move |_, _, data| {
+                if let Err(err) = data.state.do_action(action.clone()) {
+                    warn!("{err}");
+                }
+                TimeoutAction::ToDuration(interval)
+            })
+            .unwrap();
+        self.twm.key_repeat = Some((key_code, token));
+    }
+
+    fn stop_key_repeat(&mut self) {
+        if let Some((_, token)) = self.twm.key_repeat.take() {
+            self.twm.event_loop.remove(token);
+        }
+    }
+
+    /// Moves the keyboard focus to the window under the pointer, following the focus policy.
+    fn focus_follows_pointer(&mut self, location: Point<f64, Logical>) {
+        let policy = self.twm.config.focus.policy;
+        if policy == FocusPolicy::Click {
+            return;
+        }
+
+        // Moving and resizing windows or browsing menus keeps the focus where it is, and so do
+        // layer surfaces, which only take the focus on click.
+        let pointer = self.twm.seat.get_pointer().unwrap();
+        if pointer.is_grabbed() || self.twm.layer_under(location).is_some() {
+            return;
+        }
+
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        let focus = keyboard.current_focus();
+        // Title bars and borders count as part of their window.
+        match self.twm.frame_under(location) {
+            Some(window) => {
+                if focus.as_ref() != Some(window.toplevel().unwrap().wl_surface()) {
+                    let window = window.clone();
+                    self.focus_window(&window);
+                }
+            }
+            None => {
+                let window_focused = focus
+                    .as_ref()
+                    .is_some_and(|surface| self.twm.find_window(surface).is_some());
+                if policy == FocusPolicy::Strict
+                    && window_focused
+                    && self.twm.exclusive_layer_surface().is_none()
+                {
+                    keyboard.set_focus(
+                        self,
+                        Option::<WlSurface>::None,
+                        SERIAL_COUNTER.next_serial(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Runs `action`, failing with a message for the user when it can't be carried out.
+    pub fn do_action(&mut self, action: Action) -> Result<(), String> {
+        match action {
+            Action::Quit => {
+                info!("quitting");
+                self.twm.stop_signal.stop();
+            }
+            Action::CloseWindow => {
+                if let Some(window) = &self.twm.active_workspace().focus {
+                    window.toplevel().unwrap().send_close();
+                }
+            }
+            Action::Terminal => spawn(&self.twm.config.terminal)?,
+            Action::Spawn(command) => spawn(&command)?,
+            Action::ToggleFullscreen => {
+                if let Some(window) = self.twm.active_workspace().focus.clone() {
+                    let toplevel = window.toplevel().unwrap().clone();
+                    if window.is_fullscreen() {
+                        self.unfullscreen_request(toplevel);
+                    } else {
+                        self.fullscreen_request(toplevel, None);
+                    }
+                }
+            }
+            Action::ToggleFloating => {
+                if let Some(window) = self.twm.active_workspace().focus.clone() {
+                    self.twm.toggle_floating(&window);
+                }
+            }
+            Action::NextLayout => {
+                let kind = self.twm.active_workspace().layout.kind().next();
+                self.twm.set_layout(kind);
+            }
+            Action::SetLayout(kind) => self.twm.set_layout(kind),
+            Action::Layout(message) => {
+                if self
+                    .twm
+                    .active_workspace_mut()
+                    .layout
+                    .handle_message(message)
+                {
+                    self.twm.queue_refresh_geometry();
+                }
+            }
+            Action::FocusWorkspace(reference) => {
+                let idx = self.find_workspace(&reference)?;
+                self.switch_workspace(idx);
+            }
+            Action::MoveToWorkspace(reference) => {
+                let idx = self.find_workspace(&reference)?;
+                self.move_to_workspace(idx, false);
+            }
+            Action::MoveToWorkspaceAndFollow(reference) => {
+                let idx = self.find_workspace(&reference)?;
+                self.move_to_workspace(idx, true);
+            }
+            Action::FocusWindow(direction) => {
+                if let Some(window) = self.twm.window_in_direction(direction).cloned() {
+                    self.focus_window(&window);
+                    self.warp_pointer_to_focus();
+                }
+            }
+            Action::FocusLast => {
+                if let Some(window) = self.twm.active_workspace().last_focused().cloned() {
+                    self.focus_window(&window);
+                    self.warp_pointer_to_focus();
+                }
+            }
+            Action::FocusOutput(direction) => {
+                if let Some(output) = self.twm.output_in_direction(direction).cloned() {
+                    self.focus_output(&output);
+                }
+            }
+            Action::MoveToOutput(direction) => self.move_to_output(direction),
+        }
+        Ok(())
+    }
+
+    fn find_workspace(&self, reference: &WorkspaceReference) -> Result<usize, String> {
+        reference
+            .find(&self.twm.workspaces)
+            .ok_or_else(|| match reference {
+                WorkspaceReference::Index(idx) => format!("no workspace number {idx}"),
+                WorkspaceReference::Name(name) => format!("no workspace named {name:?}"),
+            })
+    }
+}
+
+fn spawn(command: &[String]) -> Result<(), String> {
+    let Some((program, args)) = command.split_first() else {
+        return Err("refusing to spawn an empty command".to_owned());
+    };
+
+    match Command::new(program).args(args).spawn() {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("error spawning {program}: {err}")),
+    }
 }
@@ -0,0 +1,272 @@
+//! The `twm msg` subcommand, talking to a running twm over its IPC socket.
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Args, Subcommand};
+use serde_json::Value;
+
+use super::{Event, EventKind, Node, Reply, Request, Response, SOCKET_PATH_ENV};
+use crate::binds::Action;
+
+#[derive(Debug, Args)]
+pub struct Msg {
+    /// Print JSON instead of a human-readable summary.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    request: MsgRequest,
+}
+
+#[derive(Debug, Subcommand)]
+enum MsgRequest {
+    /// List the windows on every workspace.
+    GetWindows,
+    /// List the outputs.
+    GetOutputs,
+    /// List the workspaces.
+    GetWorkspaces,
+    /// Print the container trees of the workspaces using the manual or bsp layout.
+    GetTree,
+    /// Print events as they happen, of every kind unless some are given.
+    Subscribe { kinds: Vec<EventKind> },
+    /// Run an action, written like `focus-workspace 2` or `spawn foot -e htop`.
+    #[command(external_subcommand)]
+    Action(Vec<String>),
+}
+
+pub fn run(msg: Msg) -> anyhow::Result<()> {
+    let request = match msg.request {
+        MsgRequest::GetWindows => Request::Windows,
+        MsgRequest::GetOutputs => Request::Outputs,
+        MsgRequest::GetWorkspaces => Request::Workspaces,
+        MsgRequest::GetTree => Request::Tree,
+        MsgRequest::Subscribe { kinds } => Request::Subscribe(kinds),
+        MsgRequest::Action(words) => Request::Action(parse_action(&words)?),
+    };
+    let subscribe = matches!(request, Request::Subscribe(_));
+
+    let path = env::var_os(SOCKET_PATH_ENV)
+        .with_context(|| format!("{SOCKET_PATH_ENV} is not set, is twm running?"))?;
+    let mut stream =
+        UnixStream::connect(&path).with_context(|| format!("error connecting to {path:?}"))?;
+
+    let mut json = serde_json::to_vec(&request)?;
+    json.push(b'\n');
+    stream
+        .write_all(&json)
+        .context("error sending the request")?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let reply = lines
+        .next()
+        .context("twm closed the connection without replying")?
+        .context("error reading the reply")?;
+    let reply: Reply = serde_json::from_str(&reply).context("error parsing the reply")?;
+    let response = reply.map_err(|err| anyhow!("twm replied with an error: {err}"))?;
+
+    if !subscribe {
+        print_response(&response, msg.json)?;
+        return Ok(());
+    }
+
+    for line in lines {
+        let line = line.context("error reading an event")?;
+        if msg.json {
+            println!("{line}");
+        } else {
+            let event: Event = serde_json::from_str(&line).context("error parsing an event")?;
+            print_event(&event);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses an action written as words, its name followed by its arguments in the shape they take
+/// in the config: `quit`, `focus-workspace 2`, `spawn foot -e htop` or `layout change-master-ratio
+/// 0.05`. `focus` is short for `focus-window`.
+fn parse_action(words: &[String]) -> anyhow::Result<Action> {
+    let (name, args) = words.split_first().context("no action given")?;
+    let name = if name == "focus" {
+        "focus-window"
+    } else {
+        name
+    };
+
+    let value = variant(name, args)?;
+    serde_json::from_value(value).with_context(|| format!("invalid action: {}", words.join(" ")))
+}
+
+/// The JSON of the enum variant `name` holding `args`.
+fn variant(name: &str, args: &[String]) -> anyhow::Result<Value> {
+    let value = match (name, args) {
+        // Spawn takes the rest of the words as its command, and layout a message of its own.
+        ("spawn", _) => Value::from(args.to_vec()),
+        ("layout", [message, args @ ..]) => variant(message, args)?,
+        (_, []) => return Ok(Value::from(name)),
+        (_, [arg]) => scalar(arg),
+        _ => bail!("{name} takes a single argument"),
+    };
+    Ok(Value::Object(
+        [(name.to_owned(), value)].into_iter().collect(),
+    ))
+}
+
+/// A number if `word` is one and a string otherwise, like `2` and `left` in the config.
+fn scalar(word: &str) -> Value {
+    serde_json::from_str(word).map_or_else(|_| Value::from(word), Value::Number)
+}
+
+fn print_response(response: &Response, json: bool) -> anyhow::Result<()> {
+    if json {
+        let value = match response {
+            Response::Handled => return Ok(()),
+            Response::Windows(windows) => serde_json::to_string_pretty(windows)?,
+            Response::Outputs(outputs) => serde_json::to_string_pretty(outputs)?,
+            Response::Workspaces(workspaces) => serde_json::to_string_pretty(workspaces)?,
+            Response::Tree(trees) => serde_json::to_string_pretty(trees)?,
+        };
+        println!("{value}");
+        return Ok(());
+    }
+
+    match response {
+        Response::Handled => (),
+        Response::Windows(windows) => {
+            for window in windows {
+                print!(
+                    "window {} {:?} ({}) on workspace {}",
+                    window.id,
+                    window.title.as_deref().unwrap_or(""),
+                    window.app_id.as_deref().unwrap_or("no app id"),
+                    window.workspace
+                );
+                if let Some(geometry) = window.geometry {
+                    print!(
+                        ", {}x{} at {},{}",
+                        geometry.width, geometry.height, geometry.x, geometry.y
+                    );
+                }
+                print_flags(&[
+                    ("focused", window.focused),
+                    ("fullscreen", window.fullscreen),
+                    ("maximized", window.maximized),
+                    ("floating", window.floating),
+                ]);
+            }
+        }
+        Response::Outputs(outputs) => {
+            for output in outputs {
+                print!(
+                    "output {} ({} {}), {}x{} at {},{}, {:.3} Hz, scale {}, workspace {}",
+                    output.name,
+                    output.make,
+                    output.model,
+                    output.geometry.width,
+                    output.geometry.height,
+                    output.geometry.x,
+                    output.geometry.y,
+                    f64::from(output.refresh) / 1000.,
+                    output.scale,
+                    output.workspace
+                );
+                print_flags(&[("focused", output.focused)]);
+            }
+        }
+        Response::Workspaces(workspaces) => {
+            for ws in workspaces {
+                print!(
+                    "workspace {} {:?}, {} layout, {} windows",
+                    ws.index,
+                    ws.name,
+                    ws.layout,
+                    ws.windows.len()
+                );
+                if let Some(output) = &ws.output {
+                    print!(", on {output}");
+                }
+                print_flags(&[("focused", ws.focused)]);
+            }
+        }
+        Response::Tree(trees) => {
+            for tree in trees {
+                println!("workspace {}", tree.workspace);
+                print_node(&tree.root, 1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints a container tree, one node per line indented by its depth.
+fn print_node(node: &Node, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match node {
+        Node::Window { id } => println!("{indent}window {id}"),
+        Node::Container {
+            mode,
+            ratios,
+            active,
+            children,
+        } => {
+            let mode = serde_json::to_value(mode).unwrap();
+            let ratios: Vec<_> = ratios.iter().map(|ratio| format!("{ratio:.2}")).collect();
+            println!(
+                "{indent}{} container, ratios {}, child {} active",
+                mode.as_str().unwrap_or_default(),
+                ratios.join(" "),
+                active
+            );
+            for child in children {
+                print_node(child, depth + 1);
+            }
+        }
+    }
+}
+
+/// Finishes a line with the names of the flags that are set.
+fn print_flags(flags: &[(&str, bool)]) {
+    for (name, set) in flags {
+        if *set {
+            print!(" [{name}]");
+        }
+    }
+    println!();
+}
+
+fn print_event(event: &Event) {
+    match event {
+        Event::WindowOpened(window) => println!("window {} opened", window.id),
+        Event::WindowClosed { id } => println!("window {id} closed"),
+        Event::WindowTitleChanged { id, title } => {
+            println!(
+                "window {id} title changed to {:?}",
+                title.as_deref().unwrap_or("")
+            )
+        }
+        Event::WindowFullscreenChanged { id, fullscreen } => {
+            let state = if *fullscreen { "entered" } else { "left" };
+            println!("window {id} {state} fullscreen");
+        }
+        Event::WindowMaximizedChanged { id, maximized } => {
+            let state = if *maximized {
+                "maximized"
+            } else {
+                "unmaximized"
+            };
+            println!("window {id} {state}");
+        }
+        Event::FocusChanged { id: Some(id) } => println!("window {id} focused"),
+        Event::FocusChanged { id: None } => println!("no window focused"),
+        Event::OutputAdded(output) => println!("output {} added", output.name),
+        Event::OutputResized(output) => println!(
+            "output {} resized to {}x{}",
+            output.name, output.geometry.width, output.geometry.height
+        ),
+        Event::ConfigReloaded => println!("config reloaded"),
+    }
+}
@@ -0,0 +1,181 @@
+//! IPC over a Unix socket.
+//!
+//! Clients write one JSON [`Request`] per line and get one JSON [`Reply`] per line back. After a
+//! [`Request::Subscribe`], the connection also receives one JSON [`Event`] per line. The path of the
+//! socket is exported to child processes in [`SOCKET_PATH_ENV`].
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use smithay::utils::Rectangle;
+
+use crate::binds::Action;
+use crate::layout::{ContainerMode, LayoutKind};
+
+pub mod client;
+pub mod server;
+
+pub const SOCKET_PATH_ENV: &str = "TWM_SOCKET";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Request {
+    /// Runs an action as if its key binding was pressed.
+    Action(Action),
+    Windows,
+    Outputs,
+    Workspaces,
+    /// The container trees of the workspaces using the manual or bsp layout.
+    Tree,
+    /// Starts streaming the events of the given kinds, or of every kind if empty.
+    Subscribe(Vec<EventKind>),
+}
+
+/// The answer to a [`Request`], serialized as `{"Ok": ...}` or `{"Err": "message"}`.
+pub type Reply = Result<Response, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Response {
+    Handled,
+    Windows(Vec<Window>),
+    Outputs(Vec<Output>),
+    Workspaces(Vec<Workspace>),
+    Tree(Vec<Tree>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    Window,
+    Focus,
+    Output,
+    Config,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    WindowOpened(Window),
+    WindowClosed {
+        id: u64,
+    },
+    WindowTitleChanged {
+        id: u64,
+        title: Option<String>,
+    },
+    WindowFullscreenChanged {
+        id: u64,
+        fullscreen: bool,
+    },
+    WindowMaximizedChanged {
+        id: u64,
+        maximized: bool,
+    },
+    /// Keyboard focus moved to the window with this id, or to no window.
+    FocusChanged {
+        id: Option<u64>,
+    },
+    OutputAdded(Output),
+    OutputResized(Output),
+    ConfigReloaded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Window {
+    pub id: u64,
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    /// Name of the workspace holding the window.
+    pub workspace: String,
+    /// Position in the global compositor space, unset while the workspace is hidden.
+    pub geometry: Option<Rect>,
+    pub focused: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
+    pub floating: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Output {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub geometry: Rect,
+    /// Refresh rate in mHz.
+    pub refresh: i32,
+    pub scale: f64,
+    /// Name of the workspace shown on the output.
+    pub workspace: String,
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    /// 1-based number of the workspace, as used by workspace actions.
+    pub index: usize,
+    pub name: String,
+    /// Name of the output showing the workspace, if any.
+    pub output: Option<String>,
+    pub focused: bool,
+    pub layout: LayoutKind,
+    /// Ids of the windows on the workspace, in layout order.
+    pub windows: Vec<u64>,
+}
+
+/// The container tree of a workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tree {
+    /// 1-based number of the workspace.
+    pub workspace: usize,
+    pub root: Node,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Node {
+    Window {
+        id: u64,
+    },
+    Container {
+        mode: ContainerMode,
+        /// Share of the container each child gets while split.
+        ratios: Vec<f64>,
+        /// Index of the child shown while tabbed or stacked, the one focused last otherwise.
+        active: usize,
+        children: Vec<Node>,
+    },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::WindowOpened(_)
+            | Event::WindowClosed { .. }
+            | Event::WindowTitleChanged { .. }
+            | Event::WindowFullscreenChanged { .. }
+            | Event::WindowMaximizedChanged { .. } => EventKind::Window,
+            Event::FocusChanged { .. } => EventKind::Focus,
+            Event::OutputAdded(_) | Event::OutputResized(_) => EventKind::Output,
+            Event::ConfigReloaded => EventKind::Config,
+        }
+    }
+}
+
+impl<Kind> From<Rectangle<i32, Kind>> for Rect {
+    fn from(rect: Rectangle<i32, Kind>) -> Self {
+        Self {
+            x: rect.loc.x,
+            y: rect.loc.y,
+            width: rect.size.w,
+            height: rect.size.h,
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use std::io::{self, Read};
+use std::net::Shutdown;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use anyhow::Context;
+use smithay::desktop::Window;
+use smithay::output::Output;
+use smithay::reexports::calloop::generic::Generic;
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+
+use super::{Event, EventKind, Reply, Request, Response, SOCKET_PATH_ENV};
+use crate::state::{State, Twm};
+use crate::window::WindowExt;
+use crate::{ipc, LoopData};
+
+/// A client that sends more than this without ending the line gets dropped instead of growing its
+/// buffer without end.
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+/// A client with this many bytes of replies and events it hasn't read yet has fallen behind, and
+/// gets dropped.
+const MAX_PENDING_LEN: usize = 1024 * 1024;
+
+pub struct IpcServer {
+    pub socket_path: PathBuf,
+    event_loop: LoopHandle<'static, LoopData>,
+    clients: Vec<Client>,
+    next_client_id: u64,
+}
+
+/// A connection to the socket.
+struct Client {
+    id: u64,
+    /// A clone of the stream its requests are read from. It shares the blocking mode of the
+    /// original, so replies and events go out with `MSG_DONTWAIT` instead, and whatever the socket
+    /// has no room for waits in `pending`.
+    stream: UnixStream,
+    /// Event kinds to send once the client subscribed, every kind if empty.
+    subscription: Option<Vec<EventKind>>,
+    pending: Vec<u8>,
+    /// Whether a source waits for the socket to become writable to send `pending`.
+    flushing: bool,
+    /// Whether the client is done sending requests.
+    closed: bool,
+}
+
+impl IpcServer {
+    /// Listens on `$XDG_RUNTIME_DIR/twm.<wayland socket>.<pid>.sock` and exports the path in
+    /// [`SOCKET_PATH_ENV`].
+    pub fn start(
+        event_loop: &LoopHandle<'static, LoopData>,
+        wayland_socket_name: &str,
+    ) -> anyhow::Result<Self> {
+        let runtime_dir = env::var_os("XDG_RUNTIME_DIR").context("XDG_RUNTIME_DIR is not set")?;
+        let socket_name = format!("twm.{wayland_socket_name}.{}.sock", process::id());
+        let socket_path = PathBuf::from(runtime_dir).join(socket_name);
+
+        let listener = UnixListener::bind(&socket_path)
+            .with_context(|| format!("error binding {socket_path:?}"))?;
+        listener.set_nonblocking(true)?;
+
+        event_loop
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                |_, listener, data| {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Some(server) = &mut data.state.twm.ipc_server {
+                                server.add_client(stream);
+                            }
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                        Err(err) => warn!("error accepting an IPC client: {err}"),
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)
+            .context("error inserting the IPC socket source")?;
+
+        env::set_var(SOCKET_PATH_ENV, &socket_path);
+        info!("listening on IPC socket: {}", socket_path.display());
+
+        Ok(Self {
+            socket_path,
+            event_loop: event_loop.clone(),
+            clients: Vec::new(),
+            next_client_id: 0,
+        })
+    }
+
+    pub fn send_event(&mut self, event: &Event) {
+        let kind = event.kind();
+        let ids: Vec<_> = self
+            .clients
+            .iter()
+            .filter(|client| client.wants(kind))
+            .map(|client| client.id)
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let mut json = serde_json::to_vec(event).unwrap();
+        json.push(b'\n');
+        for id in ids {
+            self.send(id, &json);
+        }
+    }
+
+    fn add_client(&mut self, stream: UnixStream) {
+        // The stream stays blocking: reads only happen once it is readable, and writes go through
+        // the clone without blocking.
+        let clone = match stream.try_clone() {
+            Ok(clone) => clone,
+            Err(err) => {
+                warn!("error setting up an IPC client: {err}");
+                return;
+            }
+        };
+        self.next_client_id += 1;
+        let id = self.next_client_id;
+
+        let mut buffer = Vec::new();
+        let source = Generic::new(stream, Interest::READ, Mode::Level);
+        let result = self
+            .event_loop
+            .insert_source(source, move |_, stream, data| {
+                // SAFETY: we don't drop the stream.
+                let stream = unsafe { stream.get_mut() };
+
+                let mut chunk = [0; 4096];
+                let len = match stream.read(&mut chunk) {
+                    Ok(0) => {
+                        data.state.twm.close_ipc_client(id);
+                        return Ok(PostAction::Remove);
+                    }
+                    Ok(len) => len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) => {
+                        debug!("error reading from an IPC client: {err}");
+                        data.state.twm.close_ipc_client(id);
+                        return Ok(PostAction::Remove);
+                    }
+                };
+                buffer.extend_from_slice(&chunk[..len]);
+
+                while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let reply = match serde_json::from_slice(&line) {
+                        Ok(request) => data.state.handle_ipc_request(id, request),
+                        Err(err) => Err(format!("invalid request: {err}")),
+                    };
+
+                    let Some(server) = &mut data.state.twm.ipc_server else {
+                        return Ok(PostAction::Remove);
+                    };
+                    server.send_reply(id, &reply);
+                }
+
+                if buffer.len() > MAX_REQUEST_LEN {
+                    debug!("dropping an IPC client: request over {MAX_REQUEST_LEN} bytes");
+                    if let Some(server) = &mut data.state.twm.ipc_server {
+                        server.send_reply(id, &Err("request too long".to_owned()));
+                        server.close_client(id);
+                    }
+                    return Ok(PostAction::Remove);
+                }
+
+                Ok(PostAction::Continue)
+            });
+
+        match result {
+            Ok(_) => self.clients.push(Client {
+                id,
+                stream: clone,
+                subscription: None,
+                pending: Vec::new(),
+                flushing: false,
+                closed: false,
+            }),
+            Err(err) => warn!("error inserting an IPC client source: {}", err.error),
+        }
+    }
+
+    fn subscribe(&mut self, id: u64, kinds: Vec<EventKind>) {
+        if let Some(client) = self.clients.iter_mut().find(|client| client.id == id) {
+            client.subscription = Some(kinds);
+        }
+    }
+
+    /// Forgets client `id` once the replies it is still owed are sent, unless it subscribed to
+    /// events.
+    fn close_client(&mut self, id: u64) {
+        let Some(idx) = self.clients.iter().position(|client| client.id == id) else {
+            return;
+        };
+
+        let client = &mut self.clients[idx];
+        client.closed = true;
+        if client.is_done() {
+            self.clients.remove(idx);
+        }
+    }
+
+    fn send_reply(&mut self, id: u64, reply: &Reply) {
+        let mut json = serde_json::to_vec(reply).unwrap();
+        json.push(b'\n');
+        self.send(id, &json);
+    }
+
+    /// Queues `bytes` for client `id` and sends what its socket takes of them, leaving the rest
+    /// for when it becomes writable.
+    fn send(&mut self, id: u64, bytes: &[u8]) {
+        let Some(idx) = self.clients.iter().position(|client| client.id == id) else {
+            return;
+        };
+
+        let client = &mut self.clients[idx];
+        client.pending.extend_from_slice(bytes);
+        if let Err(err) = client.flush() {
+            self.clients.remove(idx).disconnect(&err.to_string());
+            return;
+        }
+        if client.pending.len() > MAX_PENDING_LEN {
+            self.clients
+                .remove(idx)
+                .disconnect("fell behind on replies and events");
+            return;
+        }
+
+        if !client.pending.is_empty() && !client.flushing {
+            client.flushing = true;
+            let stream = client.stream.try_clone();
+            self.flush_when_writable(id, stream);
+        }
+    }
+
+    /// Sends the pending bytes of client `id` as its socket makes room for them.
+    fn flush_when_writable(&mut self, id: u64, stream: io::Result<UnixStream>) {
+        let result = stream.map_err(|err| err.to_string()).and_then(|stream| {
+            let source = Generic::new(stream, Interest::WRITE, Mode::Level);
+            self.event_loop
+                .insert_source(source, move |_, _, data| {
+                    let Some(server) = &mut data.state.twm.ipc_server else {
+                        return Ok(PostAction::Remove);
+                    };
+                    Ok(server.flush_client(id))
+                })
+                .map(drop)
+                .map_err(|err| err.error.to_string())
+        });
+
+        if let Err(err) = result {
+            if let Some(idx) = self.clients.iter().position(|client| client.id == id) {
+                self.clients.remove(idx).disconnect(&err);
+            }
+        }
+    }
+
+    /// Sends what the socket of client `id` takes of its pending bytes, and whether to keep
+    /// waiting for it to become writable.
+    fn flush_client(&mut self, id: u64) -> PostAction {
+        let Some(idx) = self.clients.iter().position(|client| client.id == id) else {
+            return PostAction::Remove;
+        };
+
+        let client = &mut self.clients[idx];
+        if let Err(err) = client.flush() {
+            self.clients.remove(idx).disconnect(&err.to_string());
+            return PostAction::Remove;
+        }
+        if client.pending.is_empty() {
+            client.flushing = false;
+            if client.is_done() {
+                self.clients.remove(idx);
+            }
+            return PostAction::Remove;
+        }
+        PostAction::Continue
+    }
+}
+
+impl Client {
+    fn wants(&self, kind: EventKind) -> bool {
+        self.subscription
+            .as_ref()
+            .is_some_and(|kinds| kinds.is_empty() || kinds.contains(&kind))
+    }
+
+    /// Whether the client sends no more requests and is owed nothing.
+    fn is_done(&self) -> bool {
+        self.closed && self.subscription.is_none() && self.pending.is_empty()
+    }
+
+    /// Sends as much of the pending bytes as the socket takes without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            // SAFETY: the buffer outlives the call, which reads at most its length.
+            let sent = unsafe {
+                libc::send(
+                    self.stream.as_raw_fd(),
+                    self.pending.as_ptr().cast(),
+                    self.pending.len(),
+                    libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                )
+            };
+            if sent < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::Interrupted => continue,
+                    io::ErrorKind::WouldBlock => break,
+                    _ => return Err(err),
+                }
+            }
+            self.pending.drain(..sent as usize);
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, reason: &str) {
+        debug!("dropping an IPC client: {reason}");
+        // Let the client know, it would otherwise wait for replies or events forever.
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+impl State {
+    fn handle_ipc_request(&mut self, client: u64, request: Request) -> Reply {
+        match request {
+            Request::Action(action) => {
+                self.do_action(action)?;
+                Ok(Response::Handled)
+            }
+            Request::Windows => Ok(Response::Windows(self.twm.ipc_windows())),
+            Request::Outputs => Ok(Response::Outputs(self.twm.ipc_outputs())),
+            Request::Workspaces => Ok(Response::Workspaces(self.twm.ipc_workspaces())),
+            Request::Tree => Ok(Response::Tree(self.twm.ipc_trees())),
+            Request::Subscribe(kinds) => {
+                if let Some(server) = &mut self.twm.ipc_server {
+                    server.subscribe(client, kinds);
+                }
+                Ok(Response::Handled)
+            }
+        }
+    }
+}
+
+impl Twm {
+    /// Sends `event` to the IPC clients subscribed to its kind.
+    pub fn send_ipc_event(&mut self, event: Event) {
+        if let Some(server) = &mut self.ipc_server {
+            server.send_event(&event);
+        }
+    }
+
+    fn close_ipc_client(&mut self, id: u64) {
+        if let Some(server) = &mut self.ipc_server {
+            server.close_client(id);
+        }
+    }
+
+    /// Describes a window on any workspace.
+    pub fn ipc_window(&self, window: &Window) -> Option<ipc::Window> {
+        let ws = self.workspaces.iter().find(|ws| ws.contains(window))?;
+        let focus = self.seat.get_keyboard().unwrap().current_focus();
+
+        Some(ipc::Window {
+            id: window.id().get(),
+            app_id: window.app_id(),
+            title: window.title(),
+            workspace: ws.name.clone(),
+            geometry: self.space.element_geometry(window).map(Into::into),
+            focused: focus.as_ref() == Some(window.toplevel().unwrap().wl_surface()),
+            fullscreen: window.is_fullscreen(),
+            maximized: window.is_maximized(),
+            floating: window.is_floating(),
+        })
+    }
+
+    pub fn ipc_output(&self, output: &Output) -> Option<ipc::Output> {
+        let state = self.output_state.get(output)?;
+        let properties = output.physical_properties();
+
+        Some(ipc::Output {
+            name: output.name(),
+            make: properties.make,
+            model: properties.model,
+            geometry: self.space.output_geometry(output)?.into(),
+            refresh: output.current_mode().map_or(0, |mode| mode.refresh),
+            scale: output.current_scale().fractional_scale(),
+            workspace: self.workspaces[state.workspace].name.clone(),
+            focused: self.active_output.as_ref() == Some(output),
+        })
+    }
+
+    fn ipc_windows(&self) -> Vec<ipc::Window> {
+        self.windows()
+            .filter_map(|window| self.ipc_window(window))
+            .collect()
+    }
+
+    fn ipc_outputs(&self) -> Vec<ipc::Output> {
+        self.output_state
+            .keys()
+            .filter_map(|output| self.ipc_output(output))
+            .collect()
+    }
+
+    fn ipc_workspaces(&self) -> Vec<ipc::Workspace> {
+        let active = self.active_workspace_idx();
+
+        self.workspaces
+            .iter()
+            .enumerate()
+            .map(|(idx, ws)| ipc::Workspace {
+                index: idx + 1,
+                name: ws.name.clone(),
+                output: self.output_for_workspace(idx).map(|output| output.name()),
+                focused: idx == active,
+                layout: ws.layout.kind(),
+                windows: ws.windows.iter().map(|window| window.id().get()).collect(),
+            })
+            .collect()
+    }
+
+    fn ipc_trees(&self) -> Vec<ipc::Tree> {
+        self.workspaces
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, ws)| {
+                Some(ipc::Tree {
+                    workspace: idx + 1,
+                    root: ws.layout.tree()?,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Rectangle, Size};
+
+use super::{
+    ratio_at, ContainerMode, Direction, Layout, LayoutKind, LayoutMessage, LayoutWindow,
+    WindowHints, WindowId,
+};
+use crate::{config, ipc};
+
+/// Windows are the leaves of a binary tree of splits, like in bspwm. A new window splits the
+/// focused one along its longest side, or the way preselected for it.
+///
+/// Splits keep their ratio until resized, whatever opens or closes elsewhere in the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bsp {
+    /// Share of a split the window that was there before keeps.
+    pub split_ratio: f64,
+    root: Option<Node>,
+    focus: Option<WindowId>,
+    /// The window new ones split: the focused one, or the one focused before when the focus is on
+    /// a window the tree doesn't hold yet.
+    anchor: Option<WindowId>,
+    preselection: Option<Preselection>,
+}
+
+/// An axis to mirror the tree along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Axis {
+    /// Swaps left and right.
+    Horizontal,
+    /// Swaps top and bottom.
+    Vertical,
+}
+
+/// Where the next window opens next to `window`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Preselection {
+    window: WindowId,
+    direction: Direction,
+    /// Share of the split `window` keeps.
+    ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Window(WindowId),
+    Split(Box<Split>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Split {
+    /// Whether the children are side by side rather than on top of each other.
+    horizontal: bool,
+    /// Share of the split the first child gets.
+    ratio: f64,
+    first: Node,
+    second: Node,
+}
+
+impl Bsp {
+    pub fn new(split_ratio: f64) -> Self {
+        Self {
+            split_ratio,
+            root: None,
+            focus: None,
+            anchor: None,
+            preselection: None,
+        }
+    }
+
+    /// Path from the root to the focused window, `false` for first children.
+    fn focus_path(&self) -> Option<Vec<bool>> {
+        self.root.as_ref()?.find(self.focus?)
+    }
+
+    /// Brings the tree in line with `windows`: closed windows leave it, their sibling taking the
+    /// place of the split, and new ones split the anchor window.
+    fn sync(&mut self, area: Rectangle<i32, Logical>, windows: &[LayoutWindow]) {
+        let keep = |id| windows.iter().any(|window: &LayoutWindow| window.id == id);
+        self.root = self.root.take().and_then(|root| root.prune(&keep));
+        self.preselection = self.preselection.filter(|p| keep(p.window));
+
+        for window in windows {
+            let Some(root) = &mut self.root else {
+                self.root = Some(Node::Window(window.id));
+                self.anchor = Some(window.id);
+                continue;
+            };
+            if root.find(window.id).is_some() {
+                continue;
+            }
+
+            // Without a window to split, the whole tree is.
+            let path = self.anchor.and_then(|id| root.find(id)).unwrap_or_default();
+            let target_area = root.area_of(&path, area);
+            let target = root.at_mut(&path);
+
+            let preselection = self
+                .preselection
+                .filter(|p| *target == Node::Window(p.window));
+            let (horizontal, new_first, ratio) = match preselection {
+                Some(preselection) => {
+                    self.preselection = None;
+                    let direction = preselection.direction;
+                    (
+                        matches!(direction, Direction::Left | Direction::Right),
+                        matches!(direction, Direction::Left | Direction::Up),
+                        preselection.ratio,
+                    )
+                }
+                None => (
+                    target_area.size.w >= target_area.size.h,
+                    false,
+                    self.split_ratio,
+                ),
+            };
+
+            let old = std::mem::replace(target, Node::Window(window.id));
+            let new = Node::Window(window.id);
+            let (first, second, ratio) = if new_first {
+                (new, old, 1. - ratio)
+            } else {
+                (old, new, ratio)
+            };
+            *target = Node::Split(Box::new(Split {
+                horizontal,
+                ratio,
+                first,
+                second,
+            }));
+            self.anchor = Some(window.id);
+        }
+
+        if self.focus_path().is_some() {
+            self.anchor = self.focus;
+        }
+    }
+
+    /// Rotates the split holding the focused window, and everything in it, by quarter turns.
+    fn rotate(&mut self, turns: i32) -> bool {
+        let Some(parent) = self.focused_parent() else {
+            return false;
+        };
+        for _ in 0..turns.rem_euclid(4) {
+            parent.rotate_clockwise();
+        }
+        true
+    }
+
+    /// Mirrors the split holding the focused window, and everything in it, along `axis`.
+    fn flip(&mut self, axis: Axis) -> bool {
+        let Some(parent) = self.focused_parent() else {
+            return false;
+        };
+        parent.flip(axis);
+        true
+    }
+
+    /// The split the focused window is a child of.
+    fn focused_parent(&mut self) -> Option<&mut Node> {
+        let mut path = self.focus_path()?;
+        path.pop()?;
+        Some(self.root.as_mut()?.at_mut(&path))
+    }
+
+    /// Adds `delta` to the share of the focused window, or of the split holding it, in the closest
+    /// split along that axis.
+    fn resize(&mut self, horizontal: bool, delta: f64) -> bool {
+        let (Some(path), Some(root)) = (self.focus_path(), &mut self.root) else {
+            return false;
+        };
+
+        for depth in (0..path.len()).rev() {
+            let Node::Split(split) = root.at_mut(&path[..depth]) else {
+                unreachable!();
+            };
+            if split.horizontal == horizontal {
+                let delta = if path[depth] { -delta } else { delta };
+                split.ratio = (split.ratio + delta).clamp(0.05, 0.95);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Preselects `direction` next to the focused window, or cancels the preselection when it is
+    /// that already.
+    fn preselect(&mut self, direction: Direction) -> bool {
+        let Some(focus) = self.focus.filter(|_| self.focus_path().is_some()) else {
+            return false;
+        };
+
+        match &mut self.preselection {
+            Some(p) if p.window == focus && p.direction == direction => self.preselection = None,
+            Some(p) if p.window == focus => p.direction = direction,
+            _ => {
+                self.preselection = Some(Preselection {
+                    window: focus,
+                    direction,
+                    ratio: self.split_ratio,
+                })
+            }
+        }
+        true
+    }
+}
+
+impl Layout for Bsp {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Bsp
+    }
+
+    fn apply_config(&mut self, config: &config::Layout) {
+        self.split_ratio = config.split_ratio;
+    }
+
+    fn set_focus(&mut self, window: Option<WindowId>) {
+        self.focus = window;
+        if self.focus_path().is_some() {
+            self.anchor = window;
+        }
+    }
+
+    fn handle_message(&mut self, message: LayoutMessage) -> bool {
+        match message {
+            LayoutMessage::Preselect(direction) => self.preselect(direction),
+            LayoutMessage::PreselectRatio(ratio) => match &mut self.preselection {
+                Some(p) if Some(p.window) == self.focus => {
+                    p.ratio = ratio.clamp(0.05, 0.95);
+                    true
+                }
+                _ => false,
+            },
+            LayoutMessage::Rotate(turns) => self.rotate(turns),
+            LayoutMessage::Flip(axis) => self.flip(axis),
+            LayoutMessage::ChangeWidth(delta) => self.resize(true, delta),
+            LayoutMessage::ChangeHeight(delta) => self.resize(false, delta),
+            _ => false,
+        }
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.sync(area, windows);
+
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+        let mut geometries = HashMap::new();
+        if let Some(root) = &self.root {
+            root.arrange(area, &hints, &mut geometries);
+        }
+
+        windows
+            .iter()
+            .map(|window| geometries[&window.id])
+            .collect()
+    }
+
+    fn move_edge(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+        window: WindowId,
+        edge: Direction,
+        to: i32,
+    ) -> bool {
+        // Windows may have come or gone since the last arrangement.
+        self.sync(area, windows);
+
+        let Some(root) = &mut self.root else {
+            return false;
+        };
+        let Some(path) = root.find(window) else {
+            return false;
+        };
+        let horizontal = matches!(edge, Direction::Left | Direction::Right);
+        // The edge faces the split the window is second in, or the one it is first in.
+        let second = matches!(edge, Direction::Left | Direction::Up);
+
+        let Some(depth) = (0..path.len()).rev().find(|&depth| {
+            let Node::Split(split) = root.at_mut(&path[..depth]) else {
+                unreachable!();
+            };
+            split.horizontal == horizontal && path[depth] == second
+        }) else {
+            return false;
+        };
+
+        let split_area = root.area_of(&path[..depth], area);
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+        let Node::Split(split) = root.at_mut(&path[..depth]) else {
+            unreachable!();
+        };
+        let first_min = split.first.min_size(&hints);
+        let second_min = split.second.min_size(&hints);
+        split.ratio = if horizontal {
+            ratio_at(
+                split_area.loc.x,
+                split_area.size.w,
+                to,
+                first_min.w,
+                second_min.w,
+            )
+        } else {
+            ratio_at(
+                split_area.loc.y,
+                split_area.size.h,
+                to,
+                first_min.h,
+                second_min.h,
+            )
+        };
+        true
+    }
+
+    fn tree(&self) -> Option<ipc::Node> {
+        let focus = self.focus_path().unwrap_or_default();
+        self.root.as_ref().map(|root| root.to_ipc(&focus))
+    }
+}
+
+impl Node {
+    /// Path of `false` for first and `true` for second children from this node to `id`.
+    fn find(&self, id: WindowId) -> Option<Vec<bool>> {
+        match self {
+            Node::Window(window) => (*window == id).then(Vec::new),
+            Node::Split(split) => [false, true].into_iter().find_map(|second| {
+                let child = if second { &split.second } else { &split.first };
+                let mut path = child.find(id)?;
+                path.insert(0, second);
+                Some(path)
+            }),
+        }
+    }
+
+    /// The node at the end of `path`.
+    fn at_mut(&mut self, path: &[bool]) -> &mut Node {
+        match (path.split_first(), self) {
+            (None, node) => node,
+            (Some((second, rest)), Node::Split(split)) => {
+                let child = if *second {
+                    &mut split.second
+                } else {
+                    &mut split.first
+                };
+                child.at_mut(rest)
+            }
+            (Some(_), Node::Window(_)) => panic!("path leads past a window"),
+        }
+    }
+
+    /// The area of the node at the end of `path` when this one fills `area`, going by the ratios
+    /// alone.
+    fn area_of(&self, path: &[bool], area: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        match (path.split_first(), self) {
+            (Some((second, rest)), Node::Split(split)) => {
+                let len = if split.horizontal {
+                    area.size.w
+                } else {
+                    area.size.h
+                };
+                let first_len = (f64::from(len) * split.ratio).round() as i32;
+                let (first, second_area) = split.divide(area, first_len);
+                let (child, child_area) = if *second {
+                    (&split.second, second_area)
+                } else {
+                    (&split.first, first)
+                };
+                child.area_of(rest, child_area)
+            }
+            _ => area,
+        }
+    }
+
+    /// Removes the windows `keep` rejects, returning what is left of the node.
+    fn prune(self, keep: &impl Fn(WindowId) -> bool) -> Option<Node> {
+        match self {
+            Node::Window(id) => keep(id).then_some(self),
+            Node::Split(split) => {
+                let Split {
+                    horizontal,
+                    ratio,
+                    first,
+                    second,
+                } = *split;
+                match (first.prune(keep), second.prune(keep)) {
+                    (Some(first), Some(second)) => Some(Node::Split(Box::new(Split {
+                        horizontal,
+                        ratio,
+                        first,
+                        second,
+                    }))),
+                    (first, second) => first.or(second),
+                }
+            }
+        }
+    }
+
+    /// The smallest size the windows in the node fit in, side by side or on top of each other as
+    /// split.
+    fn min_size(&self, hints: &HashMap<WindowId, WindowHints>) -> Size<i32, Logical> {
+        match self {
+            Node::Window(id) => hints[id].min_size,
+            Node::Split(split) => {
+                let first = split.first.min_size(hints);
+                let second = split.second.min_size(hints);
+                if split.horizontal {
+                    (first.w + second.w, first.h.max(second.h)).into()
+                } else {
+                    (first.w.max(second.w), first.h + second.h).into()
+                }
+            }
+        }
+    }
+
+    fn arrange(
+        &self,
+        area: Rectangle<i32, Logical>,
+        hints: &HashMap<WindowId, WindowHints>,
+        geometries: &mut HashMap<WindowId, Rectangle<i32, Logical>>,
+    ) {
+        match self {
+            Node::Window(id) => {
+                geometries.insert(*id, area);
+            }
+            Node::Split(split) => {
+                let first_min = split.first.min_size(hints);
+                let second_min = split.second.min_size(hints);
+                let (len, first_min, second_min) = if split.horizontal {
+                    (area.size.w, first_min.w, second_min.w)
+                } else {
+                    (area.size.h, first_min.h, second_min.h)
+                };
+
+                // The ratio gives way to the minimum sizes of the two sides, the second's first,
+                // and the sides stay within the split when those don't fit together.
+                let ratio = split.ratio.clamp(0.05, 0.95);
+                let first_len = ((f64::from(len) * ratio).round() as i32)
+                    .max(first_min)
+                    .min(len - second_min)
+                    .clamp(0, len.max(0));
+
+                let (first, second) = split.divide(area, first_len);
+                split.first.arrange(first, hints, geometries);
+                split.second.arrange(second, hints, geometries);
+            }
+        }
+    }
+
+    fn rotate_clockwise(&mut self) {
+        if let Node::Split(split) = self {
+            // Left and right become top and bottom, while top and bottom become right and left.
+            if !split.horizontal {
+                split.swap();
+            }
+            split.horizontal = !split.horizontal;
+            split.first.rotate_clockwise();
+            split.second.rotate_clockwise();
+        }
+    }
+
+    fn flip(&mut self, axis: Axis) {
+        if let Node::Split(split) = self {
+            if split.horizontal == (axis == Axis::Horizontal) {
+                split.swap();
+            }
+            split.first.flip(axis);
+            split.second.flip(axis);
+        }
+    }
+
+    /// The node in the IPC format, with splits as containers of two. `focus` is the path to the
+    /// focused window from this node.
+    fn to_ipc(&self, focus: &[bool]) -> ipc::Node {
+        match self {
+            Node::Window(id) => ipc::Node::Window { id: id.get() },
+            Node::Split(split) => {
+                let (active, rest) = match focus.split_first() {
+                    Some((second, rest)) => (usize::from(*second), rest),
+                    None => (0, &[][..]),
+                };
+                let path = |idx| if active == idx { rest } else { &[][..] };
+                ipc::Node::Container {
+                    mode: if split.horizontal {
+                        ContainerMode::SplitHorizontal
+                    } else {
+                        ContainerMode::SplitVertical
+                    },
+                    ratios: vec![split.ratio, 1. - split.ratio],
+                    active,
+                    children: vec![split.first.to_ipc(path(0)), split.second.to_ipc(path(1))],
+                }
+            }
+        }
+    }
+}
+
+impl Split {
+    /// Divides `area` along the split, giving the first child `first_len`.
+    fn divide(
+        &self,
+        area: Rectangle<i32, Logical>,
+        first_len: i32,
+    ) -> (Rectangle<i32, Logical>, Rectangle<i32, Logical>) {
+        if self.horizontal {
+            (
+                Rectangle::from_loc_and_size(area.loc, (first_len, area.size.h)),
+                Rectangle::from_loc_and_size(
+                    (area.loc.x + first_len, area.loc.y),
+                    (area.size.w - first_len, area.size.h),
+                ),
+            )
+        } else {
+            (
+                Rectangle::from_loc_and_size(area.loc, (area.size.w, first_len)),
+                Rectangle::from_loc_and_size(
+                    (area.loc.x, area.loc.y + first_len),
+                    (area.size.w, area.size.h - first_len),
+                ),
+            )
+        }
+    }
+
+    /// Swaps the children, each keeping its share.
+    fn swap(&mut self) {
+        std::mem::swap(&mut self.first, &mut self.second);
+        self.ratio = 1. - self.ratio;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{area, open, windows};
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn splits_along_the_longest_side() {
+        let mut bsp = Bsp::new(0.5);
+        assert_eq!(
+            open(&mut bsp, &windows(3)),
+            [
+                rect(10, 20, 500, 800),
+                rect(510, 20, 500, 400),
+                rect(510, 420, 500, 400),
+            ]
+        );
+    }
+
+    #[test]
+    fn preselection_places_the_next_window_with_its_ratio() {
+        let mut bsp = Bsp::new(0.5);
+        let windows = windows(3);
+        open(&mut bsp, &windows[..1]);
+
+        assert!(bsp.handle_message(LayoutMessage::Preselect(Direction::Left)));
+        assert!(bsp.handle_message(LayoutMessage::PreselectRatio(0.7)));
+        assert_eq!(
+            bsp.arrange(area(), &windows[..2]),
+            [rect(310, 20, 700, 800), rect(10, 20, 300, 800)]
+        );
+
+        // The preselection is used up, so the next window splits the longest side again.
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [
+                rect(310, 20, 700, 400),
+                rect(10, 20, 300, 800),
+                rect(310, 420, 700, 400),
+            ]
+        );
+    }
+
+    #[test]
+    fn preselecting_the_same_direction_again_cancels_it() {
+        let mut bsp = Bsp::new(0.5);
+        let windows = windows(2);
+        open(&mut bsp, &windows[..1]);
+
+        assert!(bsp.handle_message(LayoutMessage::Preselect(Direction::Up)));
+        assert!(bsp.handle_message(LayoutMessage::Preselect(Direction::Up)));
+        assert!(!bsp.handle_message(LayoutMessage::PreselectRatio(0.7)));
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [rect(10, 20, 500, 800), rect(510, 20, 500, 800)]
+        );
+    }
+
+    #[test]
+    fn rotation_turns_the_split_clockwise_keeping_shares() {
+        let mut bsp = Bsp::new(0.6);
+        let windows = windows(2);
+        open(&mut bsp, &windows);
+
+        assert!(bsp.handle_message(LayoutMessage::Rotate(1)));
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [rect(10, 20, 1000, 480), rect(10, 500, 1000, 320)]
+        );
+
+        assert!(bsp.handle_message(LayoutMessage::Rotate(1)));
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [rect(410, 20, 600, 800), rect(10, 20, 400, 800)]
+        );
+
+        // A turn the other way undoes the last one.
+        assert!(bsp.handle_message(LayoutMessage::Rotate(-1)));
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [rect(10, 20, 1000, 480), rect(10, 500, 1000, 320)]
+        );
+    }
+
+    #[test]
+    fn flipping_mirrors_only_splits_across_the_axis() {
+        let mut bsp = Bsp::new(0.6);
+        let windows = windows(2);
+        let side_by_side = open(&mut bsp, &windows);
+
+        assert!(bsp.handle_message(LayoutMessage::Flip(Axis::Vertical)));
+        assert_eq!(bsp.arrange(area(), &windows), side_by_side);
+
+        assert!(bsp.handle_message(LayoutMessage::Flip(Axis::Horizontal)));
+        assert_eq!(
+            bsp.arrange(area(), &windows),
+            [rect(410, 20, 600, 800), rect(10, 20, 400, 800)]
+        );
+    }
+
+    #[test]
+    fn rotating_and_flipping_need_a_split() {
+        let mut bsp = Bsp::new(0.5);
+        open(&mut bsp, &windows(1));
+        assert!(!bsp.handle_message(LayoutMessage::Rotate(1)));
+        assert!(!bsp.handle_message(LayoutMessage::Flip(Axis::Horizontal)));
+    }
+
+    #[test]
+    fn closing_a_window_hands_its_space_to_its_sibling() {
+        let mut bsp = Bsp::new(0.5);
+        let windows = windows(3);
+        open(&mut bsp, &windows);
+
+        let remaining = [windows[0], windows[2]];
+        assert_eq!(
+            bsp.arrange(area(), &remaining),
+            [rect(10, 20, 500, 800), rect(510, 20, 500, 800)]
+        );
+    }
+
+    #[test]
+    fn closing_a_window_keeps_the_sibling_split_and_its_ratio() {
+        let mut bsp = Bsp::new(0.5);
+        let windows = windows(3);
+        open(&mut bsp, &windows);
+        assert!(bsp.handle_message(LayoutMessage::ChangeHeight(0.25)));
+
+        assert_eq!(
+            bsp.arrange(area(), &windows[1..]),
+            [rect(10, 20, 1000, 200), rect(10, 220, 1000, 600)]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{hints, split_columns, Layout, LayoutKind, LayoutWindow};
+
+/// Every window gets a column of equal width, as far as their hints allow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Columns;
+
+impl Layout for Columns {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Columns
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        split_columns(area, &hints(windows))
+    }
+}
@@ -0,0 +1,63 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{split_columns, split_rows, Layout, LayoutKind, LayoutWindow, WindowHints};
+
+/// Each window takes half of the space left over by the previous one, alternating between
+/// vertical and horizontal splits.
+///
+/// With `spiral` set, the leftover space rotates clockwise around the first window instead of
+/// always shrinking towards the bottom-right corner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dwindle {
+    pub spiral: bool,
+}
+
+impl Layout for Dwindle {
+    fn kind(&self) -> LayoutKind {
+        if self.spiral {
+            LayoutKind::Spiral
+        } else {
+            LayoutKind::Dwindle
+        }
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let count = windows.len();
+        let mut geometries = Vec::with_capacity(count);
+        let mut rest = area;
+
+        for (i, window) in windows.iter().enumerate() {
+            if i + 1 == count {
+                geometries.push(rest);
+                break;
+            }
+
+            // Only the window's own half follows its hints, the rest is shared by the others.
+            let reversed = self.spiral && i % 4 >= 2;
+            let mut hints = [window.hints, WindowHints::default()];
+            if reversed {
+                hints.reverse();
+            }
+
+            let mut halves = if i % 2 == 0 {
+                split_columns(rest, &hints)
+            } else {
+                split_rows(rest, &hints)
+            };
+
+            // Every other pair of splits goes the other way round to form a spiral.
+            if reversed {
+                halves.reverse();
+            }
+
+            geometries.push(halves[0]);
+            rest = halves[1];
+        }
+
+        geometries
+    }
+}
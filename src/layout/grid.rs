@@ -0,0 +1,36 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{hints, split_columns, split_rows, Layout, LayoutKind, LayoutWindow, WindowHints};
+
+/// Windows are laid out in a near-square grid, filled row by row. The last row stretches its windows
+/// to the full width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Grid;
+
+impl Layout for Grid {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Grid
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let count = windows.len();
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let columns = (count as f64).sqrt().ceil() as usize;
+
+        let hints = hints(windows);
+        let row_hints: Vec<_> = hints.chunks(columns).map(WindowHints::beside).collect();
+
+        split_rows(area, &row_hints)
+            .into_iter()
+            .zip(hints.chunks(columns))
+            .flat_map(|(row, hints)| split_columns(row, hints))
+            .collect()
+    }
+}
@@ -0,0 +1,1010 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Rectangle, Size};
+
+use super::{
+    ratio_at, Direction, Layout, LayoutKind, LayoutMessage, LayoutWindow, TabBar, WindowHints,
+    WindowId,
+};
+use crate::{config, ipc};
+
+/// Windows sit in a tree of containers arranged by hand, like in i3. Each container either splits
+/// its area between its children, or shows one of them at a time under a bar of tabs.
+///
+/// New windows open next to the focused one, in its container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manual {
+    /// Height of one tab in the tab bars.
+    pub tab_height: i32,
+    root: Container,
+    focus: Option<WindowId>,
+    /// The window new ones open next to: the focused one, or the one focused before when the focus
+    /// is on a window the tree doesn't hold yet.
+    anchor: Option<WindowId>,
+    /// Tab bars, windows with a tab of their own, and windows in the background of tabbed or
+    /// stacked containers, as of the last arrangement.
+    tab_bars: Vec<TabBar>,
+    tabbed: Vec<WindowId>,
+    hidden: Vec<WindowId>,
+}
+
+/// How a container arranges its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContainerMode {
+    /// Side by side.
+    SplitHorizontal,
+    /// On top of each other.
+    SplitVertical,
+    /// One at a time, under a row of tabs.
+    Tabbed,
+    /// One at a time, under a column of title bars.
+    Stacked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Window(WindowId),
+    Container(Container),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Container {
+    mode: ContainerMode,
+    children: Vec<Node>,
+    /// Share of the container each child gets while split, adding up to 1.
+    ratios: Vec<f64>,
+    /// Index of the child shown while tabbed or stacked, the one focused last otherwise.
+    active: usize,
+}
+
+/// Where the windows go, filled while walking the tree.
+#[derive(Default)]
+struct Arrangement {
+    geometries: HashMap<WindowId, Rectangle<i32, Logical>>,
+    tab_bars: Vec<TabBar>,
+    tabbed: Vec<WindowId>,
+    hidden: Vec<WindowId>,
+}
+
+impl Manual {
+    pub fn new(tab_height: i32) -> Self {
+        Self {
+            tab_height,
+            root: Container::new(ContainerMode::SplitHorizontal),
+            focus: None,
+            anchor: None,
+            tab_bars: Vec::new(),
+            tabbed: Vec::new(),
+            hidden: Vec::new(),
+        }
+    }
+
+    /// Path from the root to the focused window.
+    fn focus_path(&self) -> Option<Vec<usize>> {
+        self.root.find(self.focus?)
+    }
+
+    /// Brings the tree in line with `windows`: closed windows leave it, and new ones are put next
+    /// to the focused window.
+    fn sync(&mut self, windows: &[LayoutWindow]) {
+        self.root
+            .prune(&|id| windows.iter().any(|window| window.id == id));
+
+        let mut after = self.anchor.filter(|id| self.root.find(*id).is_some());
+        for window in windows {
+            if self.root.find(window.id).is_some() {
+                continue;
+            }
+
+            match after.and_then(|id| self.root.find(id)) {
+                Some(path) => {
+                    let (idx, parent) = path.split_last().unwrap();
+                    self.root
+                        .container_mut(parent)
+                        .insert(idx + 1, Node::Window(window.id));
+                }
+                None => {
+                    let len = self.root.children.len();
+                    self.root.insert(len, Node::Window(window.id));
+                }
+            }
+            after = Some(window.id);
+        }
+
+        self.activate_focus();
+    }
+
+    /// The focused window along with the path to its container and its index there.
+    fn focused(&self) -> Option<(WindowId, Vec<usize>, usize)> {
+        let mut path = self.focus_path()?;
+        let idx = path.pop().unwrap();
+        Some((self.focus.unwrap(), path, idx))
+    }
+
+    /// Wraps the focused window in a new container, unless it is alone in its container already,
+    /// which then only changes mode.
+    fn split(&mut self, mode: ContainerMode) -> bool {
+        let Some((id, parent, idx)) = self.focused() else {
+            return false;
+        };
+
+        let container = self.root.container_mut(&parent);
+        if container.children.len() == 1 {
+            container.mode = mode;
+        } else {
+            let mut new = Container::new(mode);
+            new.insert(0, Node::Window(id));
+            container.children[idx] = Node::Container(new);
+        }
+        true
+    }
+
+    /// Moves the focused window in `direction`: past its neighbour, into the neighbouring
+    /// container, or out of its container into the closest one laid out along that direction.
+    fn move_window(&mut self, direction: Direction) -> bool {
+        let Some((id, parent, idx)) = self.focused() else {
+            return false;
+        };
+        let horizontal = matches!(direction, Direction::Left | Direction::Right);
+        let forward = matches!(direction, Direction::Right | Direction::Down);
+
+        // Within the container of the window.
+        let container = self.root.container_mut(&parent);
+        if container.mode.is_horizontal() == horizontal {
+            let target = if forward {
+                idx.checked_add(1).filter(|t| *t < container.children.len())
+            } else {
+                idx.checked_sub(1)
+            };
+            if let Some(target) = target {
+                match &mut container.children[target] {
+                    Node::Container(neighbour) => {
+                        let at = if forward { 0 } else { neighbour.children.len() };
+                        neighbour.insert(at, Node::Window(id));
+                        container.remove(idx);
+                    }
+                    Node::Window(_) => {
+                        container.children.swap(idx, target);
+                        container.ratios.swap(idx, target);
+                    }
+                }
+                self.activate_focus();
+                return true;
+            }
+        }
+
+        // Out into the closest ancestor along the direction. Emptied containers are only pruned
+        // once the window is back in, so that the path stays valid.
+        let path = [&parent[..], &[idx]].concat();
+        for depth in (0..parent.len()).rev() {
+            if self.root.container_mut(&path[..depth]).mode.is_horizontal() != horizontal {
+                continue;
+            }
+
+            self.root.container_mut(&parent).remove(idx);
+            let at = path[depth] + usize::from(forward);
+            self.root
+                .container_mut(&path[..depth])
+                .insert(at, Node::Window(id));
+            self.root.prune(&|_| true);
+            self.activate_focus();
+            return true;
+        }
+
+        // Nothing along the direction: the root is wrapped in a new container that is, unless the
+        // window is at the edge of the root already or alone.
+        let alone = self
+            .root
+            .children
+            .iter()
+            .map(Node::window_count)
+            .sum::<usize>()
+            == 1;
+        if self.root.mode.is_horizontal() == horizontal || alone {
+            return false;
+        }
+        self.root.container_mut(&parent).remove(idx);
+        self.root.prune(&|_| true);
+
+        let mode = if horizontal {
+            ContainerMode::SplitHorizontal
+        } else {
+            ContainerMode::SplitVertical
+        };
+        let old = std::mem::replace(&mut self.root, Container::new(mode));
+        self.root.insert(0, Node::Container(old));
+        let at = if forward { 1 } else { 0 };
+        self.root.insert(at, Node::Window(id));
+        self.activate_focus();
+        true
+    }
+
+    /// Adds `delta` to the share of the focused window, or of the container holding it, in the
+    /// closest container split along that axis.
+    fn resize(&mut self, horizontal: bool, delta: f64) -> bool {
+        let Some(path) = self.focus_path() else {
+            return false;
+        };
+
+        let mode = if horizontal {
+            ContainerMode::SplitHorizontal
+        } else {
+            ContainerMode::SplitVertical
+        };
+        for depth in (0..path.len()).rev() {
+            let container = self.root.container_mut(&path[..depth]);
+            if container.mode == mode && container.children.len() > 1 {
+                container.resize(path[depth], delta);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn activate_focus(&mut self) {
+        if let Some(path) = self.focus_path() {
+            self.root.activate(&path);
+            self.anchor = self.focus;
+        }
+    }
+}
+
+impl Layout for Manual {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Manual
+    }
+
+    fn apply_config(&mut self, config: &config::Layout) {
+        self.tab_height = config.tab_height;
+    }
+
+    fn set_focus(&mut self, window: Option<WindowId>) {
+        self.focus = window;
+        self.activate_focus();
+    }
+
+    fn handle_message(&mut self, message: LayoutMessage) -> bool {
+        match message {
+            LayoutMessage::Split(mode) => self.split(mode),
+            LayoutMessage::SetContainerMode(mode) => match self.focused() {
+                Some((_, parent, _)) => {
+                    self.root.container_mut(&parent).mode = mode;
+                    true
+                }
+                None => false,
+            },
+            LayoutMessage::ToggleSplit => match self.focused() {
+                Some((_, parent, _)) => {
+                    let container = self.root.container_mut(&parent);
+                    container.mode = if container.mode.is_horizontal() {
+                        ContainerMode::SplitVertical
+                    } else {
+                        ContainerMode::SplitHorizontal
+                    };
+                    true
+                }
+                None => false,
+            },
+            LayoutMessage::MoveWindow(direction) => self.move_window(direction),
+            LayoutMessage::ChangeWidth(delta) => self.resize(true, delta),
+            LayoutMessage::ChangeHeight(delta) => self.resize(false, delta),
+            _ => false,
+        }
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.sync(windows);
+
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+        let mut arrangement = Arrangement::default();
+        self.root
+            .arrange(area, self.tab_height, &hints, true, &mut arrangement);
+        self.tab_bars = arrangement.tab_bars;
+        self.tabbed = arrangement.tabbed;
+        self.hidden = arrangement.hidden;
+
+        windows
+            .iter()
+            .map(|window| arrangement.geometries[&window.id])
+            .collect()
+    }
+
+    fn move_edge(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+        window: WindowId,
+        edge: Direction,
+        to: i32,
+    ) -> bool {
+        // Windows may have come or gone since the last arrangement.
+        self.sync(windows);
+
+        let Some(path) = self.root.find(window) else {
+            return false;
+        };
+        let (mode, forward) = match edge {
+            Direction::Left => (ContainerMode::SplitHorizontal, false),
+            Direction::Right => (ContainerMode::SplitHorizontal, true),
+            Direction::Up => (ContainerMode::SplitVertical, false),
+            Direction::Down => (ContainerMode::SplitVertical, true),
+        };
+
+        // The closest container split along the axis with a neighbour on that side, and the
+        // index of the first of the two children the edge runs between.
+        let Some((depth, first)) = (0..path.len()).rev().find_map(|depth| {
+            let container = self.root.container_mut(&path[..depth]);
+            let idx = path[depth];
+            let first = if forward {
+                idx.checked_add(1)
+                    .filter(|next| *next < container.children.len())
+                    .map(|_| idx)
+            } else {
+                idx.checked_sub(1)
+            };
+            first
+                .filter(|_| container.mode == mode)
+                .map(|first| (depth, first))
+        }) else {
+            return false;
+        };
+
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+        let container_area = self
+            .root
+            .area_of(&path[..depth], area, self.tab_height, &hints);
+        let container = self.root.container_mut(&path[..depth]);
+        let areas = container.child_areas(container_area, self.tab_height, &hints);
+        let (before, after) = (areas[first], areas[first + 1]);
+        let min_before = container.children[first].min_size(&hints);
+        let min_after = container.children[first + 1].min_size(&hints);
+
+        // Only the two children on either side of the edge trade space.
+        let share = container.ratios[first] + container.ratios[first + 1];
+        let ratio = if mode == ContainerMode::SplitHorizontal {
+            ratio_at(
+                before.loc.x,
+                before.size.w + after.size.w,
+                to,
+                min_before.w,
+                min_after.w,
+            )
+        } else {
+            ratio_at(
+                before.loc.y,
+                before.size.h + after.size.h,
+                to,
+                min_before.h,
+                min_after.h,
+            )
+        };
+        container.ratios[first] = share * ratio;
+        container.ratios[first + 1] = share * (1. - ratio);
+        true
+    }
+
+    fn focus_neighbour(&self, window: WindowId, direction: Direction) -> Option<WindowId> {
+        let path = self.root.find(window)?;
+        let horizontal = matches!(direction, Direction::Left | Direction::Right);
+        let forward = matches!(direction, Direction::Right | Direction::Down);
+
+        // The closest container along the direction with a child on that side decides: tabs and
+        // stacked entries lead to the window they show, while splits are left to the geometry.
+        for depth in (0..path.len()).rev() {
+            let container = self.root.container(&path[..depth]);
+            if container.mode.is_horizontal() != horizontal {
+                continue;
+            }
+            let idx = path[depth];
+            let target = if forward {
+                idx.checked_add(1).filter(|t| *t < container.children.len())
+            } else {
+                idx.checked_sub(1)
+            };
+            let Some(target) = target else {
+                continue;
+            };
+            return match container.mode {
+                ContainerMode::Tabbed | ContainerMode::Stacked => {
+                    container.children[target].active_window()
+                }
+                ContainerMode::SplitHorizontal | ContainerMode::SplitVertical => None,
+            };
+        }
+        None
+    }
+
+    fn is_visible(&self, window: WindowId) -> bool {
+        !self.hidden.contains(&window)
+    }
+
+    fn has_tab(&self, window: WindowId) -> bool {
+        self.tabbed.contains(&window)
+    }
+
+    fn tab_bars(&self) -> Vec<TabBar> {
+        self.tab_bars.clone()
+    }
+
+    fn tree(&self) -> Option<ipc::Node> {
+        Some(self.root.to_ipc())
+    }
+}
+
+impl ContainerMode {
+    /// Whether the children follow each other from left to right. Tabs count as such, stacked
+    /// title bars don't.
+    fn is_horizontal(self) -> bool {
+        matches!(self, ContainerMode::SplitHorizontal | ContainerMode::Tabbed)
+    }
+}
+
+impl Node {
+    fn arrange(
+        &self,
+        area: Rectangle<i32, Logical>,
+        tab_height: i32,
+        hints: &HashMap<WindowId, WindowHints>,
+        visible: bool,
+        arrangement: &mut Arrangement,
+    ) {
+        match self {
+            Node::Window(id) => {
+                arrangement.geometries.insert(*id, area);
+                if !visible {
+                    arrangement.hidden.push(*id);
+                }
+            }
+            Node::Container(container) => {
+                container.arrange(area, tab_height, hints, visible, arrangement);
+            }
+        }
+    }
+
+    /// The smallest size the windows in the node fit in, going by how their containers arrange
+    /// them.
+    fn min_size(&self, hints: &HashMap<WindowId, WindowHints>) -> Size<i32, Logical> {
+        match self {
+            Node::Window(id) => hints[id].min_size,
+            Node::Container(container) => {
+                let sizes = container.children.iter().map(|child| child.min_size(hints));
+                sizes.fold(Size::default(), |total, size| {
+                    match container.mode {
+                        ContainerMode::SplitHorizontal => (total.w + size.w, total.h.max(size.h)),
+                        ContainerMode::SplitVertical => (total.w.max(size.w), total.h + size.h),
+                        ContainerMode::Tabbed | ContainerMode::Stacked => {
+                            (total.w.max(size.w), total.h.max(size.h))
+                        }
+                    }
+                    .into()
+                })
+            }
+        }
+    }
+
+    fn window_count(&self) -> usize {
+        match self {
+            Node::Window(_) => 1,
+            Node::Container(container) => container.children.iter().map(Node::window_count).sum(),
+        }
+    }
+
+    fn active_window(&self) -> Option<WindowId> {
+        match self {
+            Node::Window(id) => Some(*id),
+            Node::Container(container) => container.children.get(container.active)?.active_window(),
+        }
+    }
+}
+
+impl Container {
+    fn new(mode: ContainerMode) -> Self {
+        Self {
+            mode,
+            children: Vec::new(),
+            ratios: Vec::new(),
+            active: 0,
+        }
+    }
+
+    /// Path of child indices from this container to `id`.
+    fn find(&self, id: WindowId) -> Option<Vec<usize>> {
+        self.children
+            .iter()
+            .enumerate()
+            .find_map(|(idx, child)| match child {
+                Node::Window(window) => (*window == id).then(|| vec![idx]),
+                Node::Container(container) => {
+                    let mut path = container.find(id)?;
+                    path.insert(0, idx);
+                    Some(path)
+                }
+            })
+    }
+
+    /// The container at the end of `path`, which must lead to one.
+    fn container(&self, path: &[usize]) -> &Container {
+        match path.split_first() {
+            None => self,
+            Some((idx, rest)) => match &self.children[*idx] {
+                Node::Container(container) => container.container(rest),
+                Node::Window(_) => panic!("path leads to a window"),
+            },
+        }
+    }
+
+    /// The container at the end of `path`, which must lead to one.
+    fn container_mut(&mut self, path: &[usize]) -> &mut Container {
+        match path.split_first() {
+            None => self,
+            Some((idx, rest)) => match &mut self.children[*idx] {
+                Node::Container(container) => container.container_mut(rest),
+                Node::Window(_) => panic!("path leads to a window"),
+            },
+        }
+    }
+
+    /// Makes every container along `path` show the child the path goes through.
+    fn activate(&mut self, path: &[usize]) {
+        let Some((idx, rest)) = path.split_first() else {
+            return;
+        };
+        self.active = *idx;
+        if let Node::Container(container) = &mut self.children[*idx] {
+            container.activate(rest);
+        }
+    }
+
+    /// Inserts a child at `idx` with an even share, taken from the others in proportion.
+    fn insert(&mut self, idx: usize, node: Node) {
+        let count = self.children.len() as f64;
+        let share = 1. / (count + 1.);
+        for ratio in &mut self.ratios {
+            *ratio *= count / (count + 1.);
+        }
+        self.children.insert(idx, node);
+        self.ratios.insert(idx, share);
+        if self.active >= idx && self.children.len() > 1 {
+            self.active += 1;
+        }
+    }
+
+    /// Removes the child at `idx`, handing its share to the others in proportion.
+    fn remove(&mut self, idx: usize) -> Node {
+        let node = self.children.remove(idx);
+        self.ratios.remove(idx);
+        self.normalize();
+        if self.active > idx || self.active >= self.children.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        node
+    }
+
+    /// Removes the windows `keep` rejects and the containers left empty, and replaces containers
+    /// holding nothing but another container with that one.
+    fn prune(&mut self, keep: &impl Fn(WindowId) -> bool) {
+        let mut idx = 0;
+        while idx < self.children.len() {
+            let remove = match &mut self.children[idx] {
+                Node::Window(id) => !keep(*id),
+                Node::Container(container) => {
+                    container.prune(keep);
+                    container.children.is_empty()
+                }
+            };
+            if remove {
+                self.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+
+        if let [Node::Container(_)] = &self.children[..] {
+            let Some(Node::Container(only)) = self.children.pop() else {
+                unreachable!();
+            };
+            *self = only;
+        }
+    }
+
+    /// Adds `delta` to the share of the child at `idx`, taking it from the others in proportion.
+    fn resize(&mut self, idx: usize, delta: f64) {
+        let old = self.ratios[idx];
+        let new = (old + delta).clamp(0.05, 0.95);
+        let scale = (1. - new) / (1. - old);
+        for ratio in &mut self.ratios {
+            *ratio *= scale;
+        }
+        self.ratios[idx] = new;
+    }
+
+    fn normalize(&mut self) {
+        let total: f64 = self.ratios.iter().sum();
+        if total > 0. {
+            for ratio in &mut self.ratios {
+                *ratio /= total;
+            }
+        }
+    }
+
+    /// Height of the tab bar above the children, none while split.
+    fn bar_height(&self, area: Rectangle<i32, Logical>, tab_height: i32) -> i32 {
+        let rows = match self.mode {
+            ContainerMode::SplitHorizontal | ContainerMode::SplitVertical => return 0,
+            ContainerMode::Tabbed => 1,
+            ContainerMode::Stacked => self.children.len() as i32,
+        };
+        (tab_height * rows).min(area.size.h / 2)
+    }
+
+    /// The area of each child when the container fills `area`.
+    fn child_areas(
+        &self,
+        area: Rectangle<i32, Logical>,
+        tab_height: i32,
+        hints: &HashMap<WindowId, WindowHints>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        match self.mode {
+            ContainerMode::SplitHorizontal | ContainerMode::SplitVertical => {
+                let horizontal = self.mode == ContainerMode::SplitHorizontal;
+                let (start, len) = if horizontal {
+                    (area.loc.x, area.size.w)
+                } else {
+                    (area.loc.y, area.size.h)
+                };
+                let mins: Vec<_> = self
+                    .children
+                    .iter()
+                    .map(|child| {
+                        let min = child.min_size(hints);
+                        if horizontal {
+                            min.w
+                        } else {
+                            min.h
+                        }
+                    })
+                    .collect();
+
+                split_ratios(start, len, &self.ratios, &mins)
+                    .into_iter()
+                    .map(|(from, len)| {
+                        if horizontal {
+                            Rectangle::from_loc_and_size((from, area.loc.y), (len, area.size.h))
+                        } else {
+                            Rectangle::from_loc_and_size((area.loc.x, from), (area.size.w, len))
+                        }
+                    })
+                    .collect()
+            }
+            ContainerMode::Tabbed | ContainerMode::Stacked => {
+                let bar_height = self.bar_height(area, tab_height);
+                let child_area = Rectangle::from_loc_and_size(
+                    (area.loc.x, area.loc.y + bar_height),
+                    (area.size.w, area.size.h - bar_height),
+                );
+                vec![child_area; self.children.len()]
+            }
+        }
+    }
+
+    /// The area of the container at the end of `path` when this one fills `area`.
+    fn area_of(
+        &self,
+        path: &[usize],
+        area: Rectangle<i32, Logical>,
+        tab_height: i32,
+        hints: &HashMap<WindowId, WindowHints>,
+    ) -> Rectangle<i32, Logical> {
+        match path.split_first() {
+            None => area,
+            Some((idx, rest)) => {
+                let child_area = self.child_areas(area, tab_height, hints)[*idx];
+                match &self.children[*idx] {
+                    Node::Container(container) => {
+                        container.area_of(rest, child_area, tab_height, hints)
+                    }
+                    Node::Window(_) => child_area,
+                }
+            }
+        }
+    }
+
+    fn arrange(
+        &self,
+        area: Rectangle<i32, Logical>,
+        tab_height: i32,
+        hints: &HashMap<WindowId, WindowHints>,
+        visible: bool,
+        arrangement: &mut Arrangement,
+    ) {
+        let child_areas = self.child_areas(area, tab_height, hints);
+
+        let tabbed = matches!(self.mode, ContainerMode::Tabbed | ContainerMode::Stacked);
+        if tabbed && visible {
+            let bar_height = self.bar_height(area, tab_height);
+            arrangement.tab_bars.push(TabBar {
+                geometry: Rectangle::from_loc_and_size(area.loc, (area.size.w, bar_height)),
+                tabs: self
+                    .children
+                    .iter()
+                    .filter_map(Node::active_window)
+                    .collect(),
+                active: self.active,
+                stacked: self.mode == ContainerMode::Stacked,
+            });
+        }
+
+        for (idx, (child, child_area)) in self.children.iter().zip(child_areas).enumerate() {
+            // The tab shows the title of a window right under it, in place of a title bar.
+            if let (true, Node::Window(id)) = (tabbed, child) {
+                arrangement.tabbed.push(*id);
+            }
+            let visible = visible && (!tabbed || idx == self.active);
+            child.arrange(child_area, tab_height, hints, visible, arrangement);
+        }
+    }
+
+    fn to_ipc(&self) -> ipc::Node {
+        ipc::Node::Container {
+            mode: self.mode,
+            ratios: self.ratios.clone(),
+            active: self.active,
+            children: self
+                .children
+                .iter()
+                .map(|child| match child {
+                    Node::Window(id) => ipc::Node::Window { id: id.get() },
+                    Node::Container(container) => container.to_ipc(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Splits `len` pixels starting at `start` into runs of `ratios`, which add up to 1. Runs below
+/// their minimum in `mins` grow to it, taking from the others in proportion, while minimums that
+/// don't fit together are given up on.
+fn split_ratios(start: i32, len: i32, ratios: &[f64], mins: &[i32]) -> Vec<(i32, i32)> {
+    let mut pinned = vec![false; ratios.len()];
+    let lengths = loop {
+        let pinned_len: i32 = (0..mins.len())
+            .filter(|&i| pinned[i])
+            .map(|i| mins[i])
+            .sum();
+        let flexible: f64 = (0..ratios.len())
+            .filter(|&i| !pinned[i])
+            .map(|i| ratios[i])
+            .sum();
+        let lengths: Vec<f64> = (0..ratios.len())
+            .map(|i| {
+                if pinned[i] {
+                    f64::from(mins[i])
+                } else if flexible > 0. {
+                    ratios[i] / flexible * f64::from(len - pinned_len)
+                } else {
+                    0.
+                }
+            })
+            .collect();
+
+        let below_min: Vec<usize> = (0..mins.len())
+            .filter(|&i| !pinned[i] && lengths[i] < f64::from(mins[i]))
+            .collect();
+        if below_min.is_empty() {
+            break lengths;
+        }
+        if pinned_len + below_min.iter().map(|&i| mins[i]).sum::<i32>() > len {
+            break ratios.iter().map(|ratio| ratio * f64::from(len)).collect();
+        }
+        for i in below_min {
+            pinned[i] = true;
+        }
+    };
+
+    // Rounding the running total keeps the runs flush with each other.
+    let mut total: f64 = 0.;
+    lengths
+        .into_iter()
+        .map(|length| {
+            let from = total.round() as i32;
+            total += length;
+            let to = total.round() as i32;
+            (start + from, to - from)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{area, open, windows};
+    use super::*;
+
+    /// Checks every container in the tree: a ratio per child adding up to 1, the active child in
+    /// bounds, and no container holding nothing but another one.
+    fn assert_consistent(container: &Container) {
+        assert_eq!(container.ratios.len(), container.children.len());
+        if !container.children.is_empty() {
+            let total: f64 = container.ratios.iter().sum();
+            assert!((total - 1.).abs() < 1e-9, "ratios add up to {total}");
+            assert!(container.active < container.children.len());
+        }
+        assert!(
+            !matches!(&container.children[..], [Node::Container(_)]),
+            "container holding a lone container"
+        );
+        for child in &container.children {
+            if let Node::Container(child) = child {
+                assert_consistent(child);
+            }
+        }
+    }
+
+    #[test]
+    fn ratios_add_up_after_inserting_removing_and_resizing() {
+        let mut manual = Manual::new(20);
+        let windows = windows(4);
+        for n in 1..=windows.len() {
+            open(&mut manual, &windows[..n]);
+            assert_consistent(&manual.root);
+        }
+
+        assert!(manual.handle_message(LayoutMessage::ChangeWidth(0.3)));
+        assert_consistent(&manual.root);
+        assert!(manual.handle_message(LayoutMessage::ChangeWidth(-0.9)));
+        assert_consistent(&manual.root);
+        assert_eq!(manual.root.ratios[3], 0.05);
+
+        for n in (0..windows.len()).rev() {
+            manual.arrange(area(), &windows[..n]);
+            assert_consistent(&manual.root);
+        }
+    }
+
+    #[test]
+    fn new_windows_share_the_container_evenly() {
+        let mut manual = Manual::new(20);
+        let tiles = open(&mut manual, &windows(4));
+        assert!(tiles.iter().all(|tile| tile.size.w == 250));
+        assert_eq!(manual.root.ratios, [0.25; 4]);
+    }
+
+    #[test]
+    fn active_child_follows_removals() {
+        let mut manual = Manual::new(20);
+        let windows = windows(3);
+        open(&mut manual, &windows);
+        assert!(manual.handle_message(LayoutMessage::SetContainerMode(ContainerMode::Tabbed)));
+        assert_eq!(manual.root.active, 2);
+
+        // The active tab closes, and the one before it takes over.
+        manual.arrange(area(), &windows[..2]);
+        assert_eq!(manual.root.active, 1);
+        assert_consistent(&manual.root);
+
+        // A tab before the active one closes, and the active one keeps showing.
+        manual.set_focus(Some(windows[1].id));
+        manual.arrange(area(), &windows[1..2]);
+        assert_eq!(manual.root.active, 0);
+        assert!(manual.is_visible(windows[1].id));
+    }
+
+    #[test]
+    fn background_tabs_are_hidden() {
+        let mut manual = Manual::new(20);
+        let windows = windows(3);
+        open(&mut manual, &windows);
+        assert!(manual.handle_message(LayoutMessage::SetContainerMode(ContainerMode::Tabbed)));
+        manual.set_focus(Some(windows[1].id));
+        manual.arrange(area(), &windows);
+
+        assert!(!manual.is_visible(windows[0].id));
+        assert!(manual.is_visible(windows[1].id));
+        assert!(!manual.is_visible(windows[2].id));
+        assert_eq!(manual.tab_bars()[0].active, 1);
+    }
+
+    #[test]
+    fn splits_grow_windows_to_their_minimum() {
+        let mut manual = Manual::new(20);
+        let mut windows = windows(3);
+        windows[1].hints.min_size = (600, 0).into();
+        let tiles = open(&mut manual, &windows);
+        assert_eq!(
+            tiles.iter().map(|tile| tile.size.w).collect::<Vec<_>>(),
+            [200, 600, 200]
+        );
+        assert_eq!(tiles[2].loc.x + tiles[2].size.w, 1010);
+    }
+
+    #[test]
+    fn windows_under_tabs_have_a_tab() {
+        let mut manual = Manual::new(20);
+        let windows = windows(3);
+        open(&mut manual, &windows[..2]);
+        assert!(manual.handle_message(LayoutMessage::Split(ContainerMode::Tabbed)));
+        manual.arrange(area(), &windows);
+
+        assert!(!manual.has_tab(windows[0].id));
+        assert!(manual.has_tab(windows[1].id));
+        assert!(manual.has_tab(windows[2].id));
+    }
+
+    #[test]
+    fn focus_moves_between_tabs_and_stacked_entries() {
+        let mut manual = Manual::new(20);
+        let windows = windows(3);
+        open(&mut manual, &windows);
+        assert!(manual.handle_message(LayoutMessage::SetContainerMode(ContainerMode::Tabbed)));
+        manual.arrange(area(), &windows);
+
+        let [a, b, c] = [windows[0].id, windows[1].id, windows[2].id];
+        assert_eq!(manual.focus_neighbour(b, Direction::Left), Some(a));
+        assert_eq!(manual.focus_neighbour(b, Direction::Right), Some(c));
+        assert_eq!(manual.focus_neighbour(c, Direction::Right), None);
+        assert_eq!(manual.focus_neighbour(b, Direction::Up), None);
+
+        assert!(manual.handle_message(LayoutMessage::SetContainerMode(ContainerMode::Stacked)));
+        assert_eq!(manual.focus_neighbour(b, Direction::Up), Some(a));
+        assert_eq!(manual.focus_neighbour(b, Direction::Down), Some(c));
+        assert_eq!(manual.focus_neighbour(b, Direction::Left), None);
+    }
+
+    #[test]
+    fn focus_across_splits_is_left_to_the_geometry() {
+        let mut manual = Manual::new(20);
+        let windows = windows(2);
+        open(&mut manual, &windows);
+        assert_eq!(manual.focus_neighbour(windows[1].id, Direction::Left), None);
+    }
+
+    #[test]
+    fn containers_holding_a_lone_container_collapse() {
+        let mut manual = Manual::new(20);
+        let windows = windows(3);
+        open(&mut manual, &windows[..2]);
+
+        // The second window opens in a vertical split with the third.
+        manual.set_focus(Some(windows[1].id));
+        assert!(manual.handle_message(LayoutMessage::Split(ContainerMode::SplitVertical)));
+        manual.arrange(area(), &windows);
+        assert_consistent(&manual.root);
+        assert_eq!(manual.root.find(windows[2].id), Some(vec![1, 1]));
+
+        // Once the first closes, the split is all the root holds and takes its place.
+        manual.arrange(area(), &windows[1..]);
+        assert_consistent(&manual.root);
+        assert_eq!(manual.root.mode, ContainerMode::SplitVertical);
+        assert_eq!(manual.root.find(windows[2].id), Some(vec![1]));
+    }
+
+    #[test]
+    fn moving_windows_out_keeps_the_tree_consistent() {
+        let mut manual = Manual::new(20);
+        let windows = windows(4);
+        open(&mut manual, &windows[..2]);
+        assert!(manual.handle_message(LayoutMessage::Split(ContainerMode::SplitVertical)));
+        for n in 3..=windows.len() {
+            manual.arrange(area(), &windows[..n]);
+            manual.set_focus(Some(windows[n - 1].id));
+        }
+        assert_eq!(manual.root.find(windows[3].id), Some(vec![1, 2]));
+
+        for direction in [
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ] {
+            manual.handle_message(LayoutMessage::MoveWindow(direction));
+            manual.arrange(area(), &windows);
+            assert_consistent(&manual.root);
+        }
+    }
+}
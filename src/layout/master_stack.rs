@@ -0,0 +1,109 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{
+    hints, ratio_at, split_rows, Direction, Layout, LayoutKind, LayoutMessage, LayoutWindow,
+    WindowHints, WindowId,
+};
+use crate::config;
+
+/// The first `master_count` windows share a column on the left, the rest are stacked on the right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterStack {
+    pub master_count: usize,
+    /// Fraction of the width given to the master column.
+    pub master_ratio: f64,
+}
+
+impl Layout for MasterStack {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::MasterStack
+    }
+
+    fn apply_config(&mut self, config: &config::Layout) {
+        self.master_count = config.master_count;
+        self.master_ratio = config.master_ratio;
+    }
+
+    fn handle_message(&mut self, message: LayoutMessage) -> bool {
+        match message {
+            LayoutMessage::ChangeMasterCount(delta) => {
+                self.master_count = self.master_count.saturating_add_signed(delta as isize);
+            }
+            LayoutMessage::ChangeMasterRatio(delta) => {
+                self.master_ratio = (self.master_ratio + delta).clamp(0.05, 0.95);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_edge(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+        window: WindowId,
+        edge: Direction,
+        to: i32,
+    ) -> bool {
+        let masters = self.master_count.min(windows.len());
+        if masters == 0 || masters == windows.len() {
+            return false;
+        }
+        let Some(idx) = windows.iter().position(|w| w.id == window) else {
+            return false;
+        };
+
+        // Only the edge between the two columns moves.
+        match (edge, idx < masters) {
+            (Direction::Right, true) | (Direction::Left, false) => (),
+            _ => return false,
+        }
+
+        let hints = hints(windows);
+        let min_width = |hints: &[WindowHints]| hints.iter().map(|h| h.min_size.w).max().unwrap();
+        self.master_ratio = ratio_at(
+            area.loc.x,
+            area.size.w,
+            to,
+            min_width(&hints[..masters]),
+            min_width(&hints[masters..]),
+        );
+        true
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let count = windows.len();
+        let masters = self.master_count.min(count);
+        let stacked = count - masters;
+
+        // With only one of the two columns populated, it takes the whole area.
+        if masters == 0 || stacked == 0 {
+            return split_rows(area, &hints(windows));
+        }
+
+        let hints = hints(windows);
+        let min_width = |hints: &[WindowHints]| hints.iter().map(|h| h.min_size.w).max().unwrap();
+
+        // The ratio gives way to the minimum widths of the columns, the stack's first, and the
+        // columns stay within the area when those don't fit together.
+        let ratio = self.master_ratio.clamp(0.05, 0.95);
+        let master_width = ((f64::from(area.size.w) * ratio).round() as i32)
+            .max(min_width(&hints[..masters]))
+            .min(area.size.w - min_width(&hints[masters..]))
+            .clamp(0, area.size.w.max(0));
+
+        let master_area = Rectangle::from_loc_and_size(area.loc, (master_width, area.size.h));
+        let stack_area = Rectangle::from_loc_and_size(
+            (area.loc.x + master_width, area.loc.y),
+            (area.size.w - master_width, area.size.h),
+        );
+
+        let mut geometries = split_rows(master_area, &hints[..masters]);
+        geometries.extend(split_rows(stack_area, &hints[masters..]));
+        geometries
+    }
+}
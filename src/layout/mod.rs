@@ -0,0 +1,640 @@
+//! Tiling layouts.
+//!
+//! A [`Layout`] only deals with rectangles: it receives the usable area of an output and an ordered
+//! list of windows, and hands back one rectangle per window. It never touches the `Space`, so the
+//! geometry math can be exercised without a running compositor.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Rectangle, Size};
+
+use crate::{config, ipc};
+
+mod bsp;
+mod columns;
+mod dwindle;
+mod grid;
+mod manual;
+mod master_stack;
+mod monocle;
+mod scrolling;
+
+pub use bsp::{Axis, Bsp};
+pub use columns::Columns;
+pub use dwindle::Dwindle;
+pub use grid::Grid;
+pub use manual::{ContainerMode, Manual};
+pub use master_stack::MasterStack;
+pub use monocle::Monocle;
+pub use scrolling::Scrolling;
+
+/// Stable identifier of a window, independent of its position in any list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(u64);
+
+impl WindowId {
+    pub fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(1);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+/// Size constraints advertised by a window.
+///
+/// A zero dimension means "unconstrained", matching the xdg-shell semantics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowHints {
+    pub min_size: Size<i32, Logical>,
+    pub max_size: Size<i32, Logical>,
+}
+
+impl WindowHints {
+    /// The hints of windows placed side by side in a row: their widths add up, and the row is as
+    /// tall as its tallest minimum and shortest maximum allow.
+    pub fn beside(hints: &[WindowHints]) -> Self {
+        let mut row = Self::default();
+        for hints in hints {
+            row.min_size.w += hints.min_size.w;
+            row.min_size.h = row.min_size.h.max(hints.min_size.h);
+            row.max_size.h = match (row.max_size.h, hints.max_size.h) {
+                (0, max) | (max, 0) => max,
+                (a, b) => a.min(b),
+            };
+        }
+        // The row only has a maximum width if every window in it has one.
+        if hints.iter().all(|hints| hints.max_size.w > 0) {
+            row.max_size.w = hints.iter().map(|hints| hints.max_size.w).sum();
+        }
+        row
+    }
+
+    /// Grows the hints by `by` in both dimensions, leaving the missing bounds out.
+    pub fn grow(self, by: Size<i32, Logical>) -> Self {
+        let grow = |value: i32, by: i32| if value > 0 { value + by } else { 0 };
+        Self {
+            min_size: (grow(self.min_size.w, by.w), grow(self.min_size.h, by.h)).into(),
+            max_size: (grow(self.max_size.w, by.w), grow(self.max_size.h, by.h)).into(),
+        }
+    }
+
+    /// Fits a window within `area`: its size is clamped to the hints, and it is centered in
+    /// whatever room is left. Windows whose minimum is larger than `area` stick out of it evenly.
+    pub fn fit(self, area: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let clamp = |value: i32, min: i32, max: i32| {
+            let value = if max > 0 { value.min(max) } else { value };
+            value.max(min)
+        };
+        let size = Size::from((
+            clamp(area.size.w, self.min_size.w, self.max_size.w),
+            clamp(area.size.h, self.min_size.h, self.max_size.h),
+        ));
+        Rectangle::from_loc_and_size(
+            (
+                area.loc.x + (area.size.w - size.w) / 2,
+                area.loc.y + (area.size.h - size.h) / 2,
+            ),
+            size,
+        )
+    }
+
+    /// Whether the minimum size fits within `size`.
+    pub fn fits(self, size: Size<i32, Logical>) -> bool {
+        self.min_size.w <= size.w && self.min_size.h <= size.h
+    }
+}
+
+/// A bar of tabs for a layout to have drawn, one tab per child of a tabbed or stacked container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabBar {
+    pub geometry: Rectangle<i32, Logical>,
+    /// The window of each tab, the focused one for tabs holding several.
+    pub tabs: Vec<WindowId>,
+    /// Index of the tab shown.
+    pub active: usize,
+    /// Whether the tabs are on top of each other rather than side by side.
+    pub stacked: bool,
+}
+
+/// A window as seen by a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutWindow {
+    pub id: WindowId,
+    pub hints: WindowHints,
+}
+
+pub trait Layout {
+    fn kind(&self) -> LayoutKind;
+
+    /// Tells the layout which window has the focus, for those that arrange around it.
+    fn set_focus(&mut self, _window: Option<WindowId>) {}
+
+    /// Picks up the parameters of a reloaded config, keeping the arrangement built so far.
+    fn apply_config(&mut self, _config: &config::Layout) {}
+
+    /// Applies a [`LayoutMessage`], returning whether the windows need to be rearranged.
+    fn handle_message(&mut self, _message: LayoutMessage) -> bool {
+        false
+    }
+
+    /// Computes the geometry of every window in `windows`, in the same order.
+    ///
+    /// The returned rectangles tile `area` without gaps; gaps are applied by the caller with
+    /// [`with_gaps`] and [`shrink_tile`]. Tiles should stay within the windows' hints where the
+    /// area allows, the caller clamps and centers the windows in them with [`WindowHints::fit`].
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>>;
+
+    /// Moves the `edge` side of the tile of `window` to `to`, along the axis of that side, by
+    /// changing the split the tile borders on there. Sides keep clear of the minimum sizes on
+    /// either side of the split.
+    ///
+    /// Takes the same `area` and `windows` as [`Layout::arrange`], and returns whether the windows
+    /// need to be rearranged: layouts without a split there leave them.
+    fn move_edge(
+        &mut self,
+        _area: Rectangle<i32, Logical>,
+        _windows: &[LayoutWindow],
+        _window: WindowId,
+        _edge: Direction,
+        _to: i32,
+    ) -> bool {
+        false
+    }
+
+    /// The window to focus next to `window` in `direction`, for layouts that know better than the
+    /// geometry, like the next tab over. `None` leaves it to the closest window on screen.
+    fn focus_neighbour(&self, _window: WindowId, _direction: Direction) -> Option<WindowId> {
+        None
+    }
+
+    /// Whether `window` was left in view by the last arrangement. Windows behind other tabs or
+    /// scrolled out of view aren't, and get unmapped.
+    fn is_visible(&self, _window: WindowId) -> bool {
+        true
+    }
+
+    /// Whether the last arrangement put `window` right under a tab, which shows its title in
+    /// place of a title bar.
+    fn has_tab(&self, _window: WindowId) -> bool {
+        false
+    }
+
+    /// The tab bars to draw for the last arrangement, in the same coordinates as the windows.
+    fn tab_bars(&self) -> Vec<TabBar> {
+        Vec::new()
+    }
+
+    /// The tree of containers, for the layouts built on one.
+    fn tree(&self) -> Option<ipc::Node> {
+        None
+    }
+}
+
+/// Layout-specific adjustments, ignored by the layouts they don't apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutMessage {
+    /// Adds to the number of windows in the master area.
+    ChangeMasterCount(i32),
+    /// Adds to the fraction of the width given to the master area.
+    ChangeMasterRatio(f64),
+    /// Switches the focused column to the next preset width.
+    CycleColumnWidth,
+    /// Moves the focused column by that many places, negative to the left.
+    MoveColumn(i32),
+    /// Moves the top window of the column on the right to the bottom of the focused column.
+    ConsumeIntoColumn,
+    /// Moves the focused window out of its column into a new one on the right.
+    ExpelFromColumn,
+    /// Puts the focused window in a new container with that mode, so that the next windows open
+    /// next to it there.
+    Split(ContainerMode),
+    /// Changes the mode of the container of the focused window.
+    SetContainerMode(ContainerMode),
+    /// Switches the container of the focused window between splitting horizontally and
+    /// vertically.
+    ToggleSplit,
+    /// Moves the focused window past its neighbour in that direction, into a neighbouring
+    /// container or out of its own.
+    MoveWindow(Direction),
+    /// Adds to the fraction of the width given to the focused window in its container or split.
+    ChangeWidth(f64),
+    /// Adds to the fraction of the height given to the focused window in its container or split.
+    ChangeHeight(f64),
+    /// Makes the next window open on that side of the focused one, or cancels that when it is
+    /// preselected already.
+    Preselect(Direction),
+    /// Sets the share of the split the focused window keeps when the preselected one opens.
+    PreselectRatio(f64),
+    /// Rotates the split holding the focused window by that many quarter turns, negative
+    /// counterclockwise.
+    Rotate(i32),
+    /// Mirrors the split holding the focused window along that axis.
+    Flip(Axis),
+}
+
+/// The built-in layouts, used to pick one at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutKind {
+    MasterStack,
+    Columns,
+    Grid,
+    Monocle,
+    Dwindle,
+    Spiral,
+    Scrolling,
+    Manual,
+    Bsp,
+}
+
+impl LayoutKind {
+    pub const ALL: [LayoutKind; 9] = [
+        LayoutKind::MasterStack,
+        LayoutKind::Columns,
+        LayoutKind::Grid,
+        LayoutKind::Monocle,
+        LayoutKind::Dwindle,
+        LayoutKind::Spiral,
+        LayoutKind::Scrolling,
+        LayoutKind::Manual,
+        LayoutKind::Bsp,
+    ];
+
+    pub fn build(self, config: &config::Layout) -> Box<dyn Layout> {
+        match self {
+            LayoutKind::MasterStack => Box::new(MasterStack {
+                master_count: config.master_count,
+                master_ratio: config.master_ratio,
+            }),
+            LayoutKind::Columns => Box::new(Columns),
+            LayoutKind::Grid => Box::new(Grid),
+            LayoutKind::Monocle => Box::new(Monocle),
+            LayoutKind::Dwindle => Box::new(Dwindle { spiral: false }),
+            LayoutKind::Spiral => Box::new(Dwindle { spiral: true }),
+            LayoutKind::Scrolling => Box::new(Scrolling::new(
+                config.column_widths.clone(),
+                config.default_column_width,
+            )),
+            LayoutKind::Manual => Box::new(Manual::new(config.tab_height)),
+            LayoutKind::Bsp => Box::new(Bsp::new(config.split_ratio)),
+        }
+    }
+
+    /// The layout following this one in [`LayoutKind::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|k| *k == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for LayoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LayoutKind::MasterStack => "master-stack",
+            LayoutKind::Columns => "columns",
+            LayoutKind::Grid => "grid",
+            LayoutKind::Monocle => "monocle",
+            LayoutKind::Dwindle => "dwindle",
+            LayoutKind::Spiral => "spiral",
+            LayoutKind::Scrolling => "scrolling",
+            LayoutKind::Manual => "manual",
+            LayoutKind::Bsp => "bsp",
+        })
+    }
+}
+
+/// A direction on screen, used to pick neighbouring outputs and windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// Picks the candidate closest to `from` in this direction, comparing the centers of their
+    /// rectangles.
+    pub fn closest<T>(
+        self,
+        from: Rectangle<i32, Logical>,
+        candidates: impl IntoIterator<Item = (T, Rectangle<i32, Logical>)>,
+    ) -> Option<T> {
+        // Twice the centers, which keeps them whole.
+        let center = |rect: Rectangle<i32, Logical>| {
+            (
+                i64::from(rect.loc.x) * 2 + i64::from(rect.size.w),
+                i64::from(rect.loc.y) * 2 + i64::from(rect.size.h),
+            )
+        };
+        let (x, y) = center(from);
+
+        candidates
+            .into_iter()
+            .map(|(candidate, rect)| (candidate, center(rect)))
+            .filter(|(_, (cx, cy))| match self {
+                Direction::Left => *cx < x,
+                Direction::Right => *cx > x,
+                Direction::Up => *cy < y,
+                Direction::Down => *cy > y,
+            })
+            .min_by_key(|(_, (cx, cy))| (cx - x).pow(2) + (cy - y).pow(2))
+            .map(|(candidate, _)| candidate)
+    }
+}
+
+/// Shrinks the output area so that tiles shrunk with [`shrink_tile`] end up `gap` pixels away from
+/// the edges and from each other.
+pub fn with_gaps(area: Rectangle<i32, Logical>, gap: i32) -> Rectangle<i32, Logical> {
+    let half = gap / 2;
+    Rectangle::from_loc_and_size(
+        (area.loc.x + gap - half, area.loc.y + gap - half),
+        (area.size.w - gap, area.size.h - gap),
+    )
+}
+
+/// Shrinks a tile returned by a layout so that neighbours are `gap` pixels apart.
+pub fn shrink_tile(tile: Rectangle<i32, Logical>, gap: i32) -> Rectangle<i32, Logical> {
+    let half = gap / 2;
+    Rectangle::from_loc_and_size(
+        (tile.loc.x + half, tile.loc.y + half),
+        ((tile.size.w - gap).max(1), (tile.size.h - gap).max(1)),
+    )
+}
+
+/// Splits `len` pixels starting at `start` into `count` runs, handing the remainder to the first
+/// runs so that the result covers the whole span.
+pub(super) fn split_evenly(start: i32, len: i32, count: usize) -> impl Iterator<Item = (i32, i32)> {
+    let count = count as i32;
+    let base = len / count.max(1);
+    let extra = len % count.max(1);
+
+    (0..count).scan(start, move |pos, i| {
+        let run = base + i32::from(i < extra);
+        let item = (*pos, run);
+        *pos += run;
+        Some(item)
+    })
+}
+
+/// Splits `len` pixels starting at `start` into one run per `(min, max)` pair, where 0 leaves a
+/// bound out.
+///
+/// Runs are as even as their bounds allow: those that would end up below their minimum or above
+/// their maximum are pinned to it, and the others share what is left. Maximums can leave the end
+/// of the span unused, while minimums that don't fit together are given up on.
+pub(super) fn split_bounded(start: i32, len: i32, bounds: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut runs = vec![None; bounds.len()];
+
+    loop {
+        let pinned: i32 = runs.iter().flatten().sum();
+        let flexible: Vec<usize> = (0..runs.len()).filter(|&i| runs[i].is_none()).collect();
+        if flexible.is_empty() {
+            break;
+        }
+
+        let shares: Vec<(usize, i32)> = flexible
+            .iter()
+            .zip(split_evenly(0, len - pinned, flexible.len()))
+            .map(|(&i, (_, share))| (i, share))
+            .collect();
+
+        let below_min: Vec<usize> = shares
+            .iter()
+            .filter(|&&(i, share)| share < bounds[i].0)
+            .map(|&(i, _)| i)
+            .collect();
+        let min_total: i32 = below_min.iter().map(|&i| bounds[i].0).sum();
+        if !below_min.is_empty() && pinned + min_total <= len {
+            for i in below_min {
+                runs[i] = Some(bounds[i].0);
+            }
+            continue;
+        }
+
+        let above_max: Vec<usize> = shares
+            .iter()
+            .filter(|&&(i, share)| bounds[i].1 > 0 && share > bounds[i].1)
+            .map(|&(i, _)| i)
+            .collect();
+        if !above_max.is_empty() {
+            for i in above_max {
+                runs[i] = Some(bounds[i].1);
+            }
+            continue;
+        }
+
+        for (i, share) in shares {
+            runs[i] = Some(share);
+        }
+    }
+
+    runs.into_iter()
+        .flatten()
+        .scan(start, |pos, run| {
+            let item = (*pos, run);
+            *pos += run;
+            Some(item)
+        })
+        .collect()
+}
+
+/// Splits `area` into a column with one row per window, sized within their hints.
+pub(super) fn split_rows(
+    area: Rectangle<i32, Logical>,
+    hints: &[WindowHints],
+) -> Vec<Rectangle<i32, Logical>> {
+    let bounds: Vec<_> = hints
+        .iter()
+        .map(|hints| (hints.min_size.h, hints.max_size.h))
+        .collect();
+    split_bounded(area.loc.y, area.size.h, &bounds)
+        .into_iter()
+        .map(|(y, h)| Rectangle::from_loc_and_size((area.loc.x, y), (area.size.w, h)))
+        .collect()
+}
+
+/// Splits `area` into a row with one column per window, sized within their hints.
+pub(super) fn split_columns(
+    area: Rectangle<i32, Logical>,
+    hints: &[WindowHints],
+) -> Vec<Rectangle<i32, Logical>> {
+    let bounds: Vec<_> = hints
+        .iter()
+        .map(|hints| (hints.min_size.w, hints.max_size.w))
+        .collect();
+    split_bounded(area.loc.x, area.size.w, &bounds)
+        .into_iter()
+        .map(|(x, w)| Rectangle::from_loc_and_size((x, area.loc.y), (w, area.size.h)))
+        .collect()
+}
+
+/// The share of `len` pixels starting at `start` that ends at `to`, keeping `min_before` pixels
+/// before and `min_after` after it, the latter first when both don't fit.
+pub(super) fn ratio_at(start: i32, len: i32, to: i32, min_before: i32, min_after: i32) -> f64 {
+    if len <= 0 {
+        return 0.5;
+    }
+    let at = (to - start).max(min_before).min(len - min_after);
+    (f64::from(at) / f64::from(len)).clamp(0.05, 0.95)
+}
+
+/// The hints of every window in `windows`, for [`split_rows`] and [`split_columns`].
+pub(super) fn hints(windows: &[LayoutWindow]) -> Vec<WindowHints> {
+    windows.iter().map(|window| window.hints).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn area() -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((10, 20), (1000, 800))
+    }
+
+    pub(super) fn windows(count: usize) -> Vec<LayoutWindow> {
+        (0..count)
+            .map(|_| LayoutWindow {
+                id: WindowId::next(),
+                hints: WindowHints::default(),
+            })
+            .collect()
+    }
+
+    pub(super) fn overlap(a: Rectangle<i32, Logical>, b: Rectangle<i32, Logical>) -> bool {
+        a.loc.x < b.loc.x + b.size.w
+            && b.loc.x < a.loc.x + a.size.w
+            && a.loc.y < b.loc.y + b.size.h
+            && b.loc.y < a.loc.y + a.size.h
+    }
+
+    /// Opens `windows` one by one, each next to the one before it, which has the focus, and
+    /// arranges them all.
+    pub(super) fn open(
+        layout: &mut dyn Layout,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        for n in 1..windows.len() {
+            layout.set_focus(Some(windows[n - 1].id));
+            layout.arrange(area(), &windows[..n]);
+        }
+        layout.set_focus(windows.last().map(|w| w.id));
+        layout.arrange(area(), windows)
+    }
+
+    /// Arranges `count` windows, focusing each as it opens.
+    fn arrange(kind: LayoutKind, count: usize) -> Vec<Rectangle<i32, Logical>> {
+        let mut layout = kind.build(&config::Layout::default());
+        open(&mut *layout, &windows(count))
+    }
+
+    #[test]
+    fn split_bounded_shares_evenly_and_rounds_into_the_first_runs() {
+        assert_eq!(
+            split_bounded(10, 100, &[(0, 0); 3]),
+            [(10, 34), (44, 33), (77, 33)]
+        );
+    }
+
+    #[test]
+    fn split_bounded_pins_runs_to_their_minimum() {
+        assert_eq!(
+            split_bounded(0, 100, &[(60, 0), (0, 0), (0, 0)]),
+            [(0, 60), (60, 20), (80, 20)]
+        );
+    }
+
+    #[test]
+    fn split_bounded_pins_runs_to_their_maximum() {
+        assert_eq!(
+            split_bounded(0, 100, &[(0, 20), (0, 0)]),
+            [(0, 20), (20, 80)]
+        );
+        // With every run at its maximum, the end of the span is left unused.
+        assert_eq!(
+            split_bounded(0, 100, &[(0, 20), (0, 30)]),
+            [(0, 20), (20, 30)]
+        );
+    }
+
+    #[test]
+    fn split_bounded_gives_up_on_minimums_that_dont_fit() {
+        assert_eq!(
+            split_bounded(0, 100, &[(80, 0), (80, 0)]),
+            [(0, 50), (50, 50)]
+        );
+    }
+
+    #[test]
+    fn split_bounded_without_runs() {
+        assert!(split_bounded(0, 100, &[]).is_empty());
+    }
+
+    #[test]
+    fn no_windows_give_no_tiles() {
+        for kind in LayoutKind::ALL {
+            assert!(arrange(kind, 0).is_empty(), "{kind}");
+        }
+    }
+
+    #[test]
+    fn tiles_cover_the_area_without_overlapping() {
+        let tiling = LayoutKind::ALL
+            .into_iter()
+            .filter(|kind| !matches!(kind, LayoutKind::Monocle | LayoutKind::Scrolling));
+        for kind in tiling {
+            for count in 1..=7 {
+                let tiles = arrange(kind, count);
+                assert_eq!(tiles.len(), count, "{kind} with {count}");
+
+                for (i, tile) in tiles.iter().enumerate() {
+                    assert!(area().contains_rect(*tile), "{kind} with {count}: {tile:?}");
+                    for other in &tiles[i + 1..] {
+                        assert!(!overlap(*tile, *other), "{kind} with {count}");
+                    }
+                }
+
+                let covered: i32 = tiles.iter().map(|tile| tile.size.w * tile.size.h).sum();
+                assert_eq!(
+                    covered,
+                    area().size.w * area().size.h,
+                    "{kind} with {count}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_stay_in_the_area_when_minimums_dont_fit() {
+        for kind in [LayoutKind::MasterStack, LayoutKind::Manual, LayoutKind::Bsp] {
+            let mut layout = kind.build(&config::Layout::default());
+            let mut windows = windows(2);
+            for window in &mut windows {
+                window.hints.min_size = (1200, 0).into();
+            }
+            for tile in layout.arrange(area(), &windows) {
+                assert!(area().contains_rect(tile), "{kind}: {tile:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn monocle_gives_every_window_the_area() {
+        for count in 1..=3 {
+            assert!(arrange(LayoutKind::Monocle, count)
+                .into_iter()
+                .all(|tile| tile == area()));
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use smithay::utils::{Logical, Rectangle};
+
+use super::{Layout, LayoutKind, LayoutWindow};
+
+/// Every window covers the whole area; stacking decides which one is visible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Monocle
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        vec![area; windows.len()]
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use smithay::utils::{Logical, Rectangle};
+
+use super::{
+    split_rows, Direction, Layout, LayoutKind, LayoutMessage, LayoutWindow, WindowHints, WindowId,
+};
+use crate::config;
+
+/// Windows sit in columns on an endless horizontal strip, of which the area shows a part.
+///
+/// Opening a window adds a column next to the focused one instead of resizing the others, and the
+/// view scrolls to keep the focused column visible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scrolling {
+    /// Preset column widths, as fractions of the area width, that columns cycle through.
+    pub widths: Vec<f64>,
+    /// Width of new columns, as a fraction of the area width.
+    pub default_width: f64,
+    columns: Vec<Column>,
+    focus: Option<WindowId>,
+    /// Position of the left edge of the view on the strip.
+    camera: i32,
+    /// Windows in columns scrolled wholly out of view, as of the last arrangement.
+    hidden: Vec<WindowId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Column {
+    /// Windows from top to bottom.
+    windows: Vec<WindowId>,
+    /// Fraction of the area width.
+    width: f64,
+}
+
+impl Scrolling {
+    pub fn new(widths: Vec<f64>, default_width: f64) -> Self {
+        Self {
+            widths,
+            default_width,
+            columns: Vec::new(),
+            focus: None,
+            camera: 0,
+            hidden: Vec::new(),
+        }
+    }
+
+    fn focused_column(&self) -> Option<usize> {
+        let focus = self.focus?;
+        self.columns.iter().position(|c| c.windows.contains(&focus))
+    }
+
+    /// Brings the columns in line with `windows`: closed windows leave their column, and new ones
+    /// get a column of their own right after the focused one.
+    fn sync(&mut self, windows: &[LayoutWindow]) {
+        for column in &mut self.columns {
+            column
+                .windows
+                .retain(|id| windows.iter().any(|w| w.id == *id));
+        }
+        self.columns.retain(|column| !column.windows.is_empty());
+
+        let mut idx = self
+            .focused_column()
+            .map_or(self.columns.len(), |idx| idx + 1);
+        for window in windows {
+            if self.columns.iter().any(|c| c.windows.contains(&window.id)) {
+                continue;
+            }
+            let column = Column {
+                windows: vec![window.id],
+                width: self.default_width,
+            };
+            self.columns.insert(idx, column);
+            idx += 1;
+        }
+    }
+
+    /// The smallest width of each column, that of its widest window.
+    fn min_widths(&self, hints: &HashMap<WindowId, WindowHints>) -> Vec<i32> {
+        self.columns
+            .iter()
+            .map(|column| {
+                let min_width = column.windows.iter().map(|id| hints[id].min_size.w).max();
+                min_width.unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// Positions and widths of the columns on the strip. Columns are never narrower than their
+    /// windows need, nor wider than the area.
+    fn spans(&self, area: Rectangle<i32, Logical>, min_widths: &[i32]) -> Vec<(i32, i32)> {
+        let mut x = 0;
+        self.columns
+            .iter()
+            .zip(min_widths)
+            .map(|(column, min_width)| {
+                let width = ((f64::from(area.size.w) * column.width).round() as i32)
+                    .max(*min_width)
+                    .min(area.size.w);
+                let item = (x, width);
+                x += width;
+                item
+            })
+            .collect()
+    }
+}
+
+impl Layout for Scrolling {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Scrolling
+    }
+
+    fn apply_config(&mut self, config: &config::Layout) {
+        self.widths.clone_from(&config.column_widths);
+        self.default_width = config.default_column_width;
+    }
+
+    fn set_focus(&mut self, window: Option<WindowId>) {
+        self.focus = window;
+    }
+
+    fn handle_message(&mut self, message: LayoutMessage) -> bool {
+        let Some(idx) = self.focused_column() else {
+            return false;
+        };
+
+        match message {
+            LayoutMessage::CycleColumnWidth => {
+                // The next larger preset, or back to the smallest one.
+                let width = self.columns[idx].width;
+                let next = self
+                    .widths
+                    .iter()
+                    .copied()
+                    .filter(|w| *w > width + 0.001)
+                    .reduce(f64::min)
+                    .or_else(|| self.widths.iter().copied().reduce(f64::min));
+                match next {
+                    Some(next) => self.columns[idx].width = next,
+                    None => return false,
+                }
+            }
+            LayoutMessage::MoveColumn(delta) => {
+                let last = self.columns.len() as i32 - 1;
+                let target = (idx as i32 + delta).clamp(0, last) as usize;
+                if target == idx {
+                    return false;
+                }
+                let column = self.columns.remove(idx);
+                self.columns.insert(target, column);
+            }
+            LayoutMessage::ConsumeIntoColumn => {
+                let Some(next) = self.columns.get_mut(idx + 1) else {
+                    return false;
+                };
+                let window = next.windows.remove(0);
+                if next.windows.is_empty() {
+                    self.columns.remove(idx + 1);
+                }
+                self.columns[idx].windows.push(window);
+            }
+            LayoutMessage::ExpelFromColumn => {
+                let column = &mut self.columns[idx];
+                if column.windows.len() < 2 {
+                    return false;
+                }
+                let focus = self.focus.unwrap();
+                column.windows.retain(|id| *id != focus);
+                let column = Column {
+                    windows: vec![focus],
+                    width: self.default_width,
+                };
+                self.columns.insert(idx + 1, column);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_edge(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+        window: WindowId,
+        edge: Direction,
+        to: i32,
+    ) -> bool {
+        // Windows may have come or gone since the last arrangement.
+        self.sync(windows);
+
+        let Some(idx) = self
+            .columns
+            .iter()
+            .position(|c| c.windows.contains(&window))
+        else {
+            return false;
+        };
+        if area.size.w <= 0 {
+            return false;
+        }
+
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+        let min_widths = self.min_widths(&hints);
+        let (x, old_width) = self.spans(area, &min_widths)[idx];
+        let left = area.loc.x + x - self.camera;
+        let width = match edge {
+            Direction::Right => to - left,
+            // The view scrolls along so that the right edge stays put.
+            Direction::Left => left + old_width - to,
+            _ => return false,
+        };
+        let width = width.max(min_widths[idx]).min(area.size.w);
+        if edge == Direction::Left {
+            self.camera += width - old_width;
+        }
+        self.columns[idx].width = f64::from(width) / f64::from(area.size.w);
+        true
+    }
+
+    fn arrange(
+        &mut self,
+        area: Rectangle<i32, Logical>,
+        windows: &[LayoutWindow],
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.sync(windows);
+
+        let hints: HashMap<_, _> = windows.iter().map(|w| (w.id, w.hints)).collect();
+
+        let columns = self.spans(area, &self.min_widths(&hints));
+        let end = columns.last().map_or(0, |(x, width)| x + width);
+
+        // Scroll as little as possible to show the focused column whole.
+        self.camera = self.camera.min(end - area.size.w).max(0);
+        if let Some(idx) = self.focused_column() {
+            let (x, width) = columns[idx];
+            if x < self.camera {
+                self.camera = x;
+            } else if x + width > self.camera + area.size.w {
+                self.camera = x + width - area.size.w;
+            }
+        }
+
+        let mut geometries = HashMap::new();
+        self.hidden.clear();
+        for (column, (x, width)) in self.columns.iter().zip(columns) {
+            // Out of view, the windows would only reach into the neighbouring outputs.
+            if x + width <= self.camera || x >= self.camera + area.size.w {
+                self.hidden.extend(&column.windows);
+            }
+
+            let column_area = Rectangle::from_loc_and_size(
+                (area.loc.x + x - self.camera, area.loc.y),
+                (width, area.size.h),
+            );
+            let column_hints: Vec<WindowHints> =
+                column.windows.iter().map(|id| hints[id]).collect();
+            geometries.extend(
+                column
+                    .windows
+                    .iter()
+                    .copied()
+                    .zip(split_rows(column_area, &column_hints)),
+            );
+        }
+
+        windows
+            .iter()
+            .map(|window| geometries[&window.id])
+            .collect()
+    }
+
+    fn is_visible(&self, window: WindowId) -> bool {
+        !self.hidden.contains(&window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{area, open, overlap, windows};
+    use super::*;
+
+    fn scrolling() -> Scrolling {
+        Scrolling::new(vec![1. / 3., 0.5, 2. / 3.], 0.5)
+    }
+
+    #[test]
+    fn columns_dont_overlap_and_show_the_focus() {
+        for count in 1..=5 {
+            let tiles = open(&mut scrolling(), &windows(count));
+            assert!(area().contains_rect(*tiles.last().unwrap()), "{count}");
+            for (i, tile) in tiles.iter().enumerate() {
+                assert_eq!(tile.loc.y, area().loc.y);
+                assert_eq!(tile.size.h, area().size.h);
+                for other in &tiles[i + 1..] {
+                    assert!(!overlap(*tile, *other), "{count}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn columns_out_of_view_are_hidden() {
+        let mut scrolling = scrolling();
+        let windows = windows(6);
+        let tiles = open(&mut scrolling, &windows);
+
+        for (window, tile) in windows.iter().zip(tiles) {
+            let in_view = tile.loc.x < area().loc.x + area().size.w
+                && tile.loc.x + tile.size.w > area().loc.x;
+            assert_eq!(scrolling.is_visible(window.id), in_view, "{tile:?}");
+        }
+        assert!(!scrolling.is_visible(windows[0].id));
+        assert!(scrolling.is_visible(windows[5].id));
+    }
+}
@@ -4,26 +4,85 @@ extern crate tracing;
 mod handlers;
 
 mod backend;
+mod binds;
+mod config;
+mod cursor;
+mod decoration;
 mod grabs;
 mod input;
+mod ipc;
+mod layout;
+mod rules;
 mod state;
+mod window;
+mod workspace;
 
-use std::env;
+use std::path::PathBuf;
+use std::{env, process, thread};
 
+use backend::BackendKind;
+use clap::{Parser, Subcommand};
+use config::Config;
+use smithay::desktop::layer_map_for_output;
+use smithay::reexports::calloop::channel::{self, Channel};
 use smithay::reexports::calloop::EventLoop;
 use smithay::reexports::wayland_server::Display;
 use state::State;
 pub use state::Twm;
 use tracing_subscriber::EnvFilter;
 
+#[derive(Debug, Parser)]
+#[command(version, about = "A toy wayland compositor.")]
+struct Cli {
+    /// Backend to run on, picked from the environment by default.
+    #[arg(long, env = "TWM_BACKEND")]
+    backend: Option<BackendKind>,
+    /// Name of the Wayland socket, the first free `wayland-N` by default.
+    #[arg(long)]
+    socket: Option<String>,
+    /// Config file to use instead of `$XDG_CONFIG_HOME/twm/config.toml`.
+    #[arg(long, short)]
+    config: Option<PathBuf>,
+    /// Log filter, overriding `RUST_LOG`.
+    #[arg(long)]
+    log: Option<String>,
+    /// Exit when COMMAND exits.
+    #[arg(long, requires = "spawn")]
+    session: bool,
+    /// Command to spawn once the sockets are ready.
+    #[arg(last = true, value_name = "COMMAND")]
+    spawn: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send a request to the running twm over IPC.
+    Msg(ipc::client::Msg),
+}
+
 pub struct LoopData {
     state: State,
 }
 
 fn main() {
+    let cli = Cli::parse();
+
+    if let Some(Command::Msg(msg)) = cli.command {
+        if let Err(err) = ipc::client::run(msg) {
+            eprintln!("error: {err:#}");
+            process::exit(1);
+        }
+        return;
+    }
+
     env::set_var("RUST_BACKTRACE", "1");
 
-    let directives = env::var("RUST_LOG").unwrap_or_else(|_| "twm=debug,info".to_owned());
+    let directives = cli
+        .log
+        .or_else(|| env::var("RUST_LOG").ok())
+        .unwrap_or_else(|| "twm=debug,info".to_owned());
     let env_filter = EnvFilter::builder().parse_lossy(directives);
     tracing_subscriber::fmt()
         .compact()
@@ -33,8 +92,34 @@ fn main() {
     let _client = tracy_client::Client::start();
 
     let mut event_loop: EventLoop<LoopData> = EventLoop::try_new().unwrap();
+    let config_path = cli.config.or_else(Config::default_path);
+    let config = Config::load_or_default(config_path.as_deref());
+
     let display = Display::new().unwrap();
-    let state = State::new(event_loop.handle(), event_loop.get_signal(), display);
+    let state = State::new(
+        event_loop.handle(),
+        event_loop.get_signal(),
+        display,
+        config,
+        cli.backend,
+        cli.socket,
+    );
+    let state = match state {
+        Ok(state) => state,
+        Err(err) => {
+            error!("{err:#}");
+            process::exit(1);
+        }
+    };
+
+    if let Some(path) = config_path {
+        config::watch(&event_loop.handle(), path);
+    }
+
+    // The sockets are set up and exported in the environment by now.
+    if !cli.spawn.is_empty() {
+        spawn_client(&event_loop, &cli.spawn, cli.session);
+    }
 
     let mut data = LoopData { state };
 
@@ -43,7 +128,11 @@ fn main() {
             let _span = tracy_client::span!("loop callback");
 
             data.state.twm.space.refresh();
+            data.state.twm.refresh_window_outputs();
             data.state.twm.popups.cleanup();
+            for output in data.state.twm.space.outputs() {
+                layer_map_for_output(output).cleanup();
+            }
 
             {
                 let _span = tracy_client::span!("flush_clients");
@@ -52,3 +141,43 @@ fn main() {
         })
         .unwrap();
 }
+
+/// Spawns `command`, stopping the compositor once it exits if `session` is set.
+fn spawn_client(event_loop: &EventLoop<LoopData>, command: &[String], session: bool) {
+    let (program, args) = command.split_first().unwrap();
+    let mut child = match process::Command::new(program).args(args).spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            error!("error spawning {program}: {err}");
+            if session {
+                process::exit(1);
+            }
+            return;
+        }
+    };
+
+    if !session {
+        return;
+    }
+
+    // Wait on a thread and wake up the event loop when the session command is done.
+    let (sender, channel): (_, Channel<()>) = channel::channel();
+    let program = program.clone();
+    thread::spawn(move || {
+        match child.wait() {
+            Ok(status) => info!("{program} exited with {status}"),
+            Err(err) => warn!("error waiting for {program}: {err}"),
+        }
+        let _ = sender.send(());
+    });
+
+    event_loop
+        .handle()
+        .insert_source(channel, |event, _, data| {
+            if let channel::Event::Msg(()) = event {
+                info!("session command exited, quitting");
+                data.state.twm.stop_signal.stop();
+            }
+        })
+        .unwrap();
+}
@@ -0,0 +1,243 @@
+//! Window rules: settings for the windows whose app id and title match, from the config.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::workspace::WorkspaceReference;
+
+/// Settings applied to the windows matching `app_id` and `title`. A rule without either matches
+/// every window.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WindowRule {
+    pub app_id: Option<Match>,
+    pub title: Option<Match>,
+    /// Workspace the window opens on.
+    pub workspace: Option<WorkspaceReference>,
+    /// Name of the output whose workspace the window opens on, unless `workspace` is set.
+    pub output: Option<String>,
+    pub floating: Option<bool>,
+    /// Width and height of the window while floating.
+    pub size: Option<[i32; 2]>,
+    pub opacity: Option<f32>,
+    pub fullscreen: Option<bool>,
+    /// Whether the window takes the focus when it opens.
+    pub focus: Option<bool>,
+}
+
+/// Matches a string either exactly or, written as `{ regex = "..." }`, against a regex. Regexes
+/// match anywhere in the string unless anchored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "MatchConfig")]
+pub enum Match {
+    Exact(String),
+    Regex(Regex),
+}
+
+/// A [`Match`] as written in the config, before its regex is compiled.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MatchConfig {
+    Exact(String),
+    Regex { regex: String },
+}
+
+#[derive(Clone)]
+pub struct Regex(regex::Regex);
+
+/// The settings of every rule matching a window, later rules overriding earlier ones.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedRules {
+    pub workspace: Option<WorkspaceReference>,
+    pub output: Option<String>,
+    pub floating: Option<bool>,
+    pub size: Option<[i32; 2]>,
+    pub opacity: Option<f32>,
+    pub fullscreen: Option<bool>,
+    pub focus: Option<bool>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, app_id: Option<&str>, title: Option<&str>) -> bool {
+        let matches = |matcher: &Option<Match>, value: Option<&str>| match matcher {
+            None => true,
+            Some(matcher) => value.is_some_and(|value| matcher.matches(value)),
+        };
+        matches(&self.app_id, app_id) && matches(&self.title, title)
+    }
+}
+
+impl Match {
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Match::Exact(expected) => value == expected,
+            Match::Regex(regex) => regex.0.is_match(value),
+        }
+    }
+}
+
+impl ResolvedRules {
+    pub fn new(rules: &[WindowRule], app_id: Option<&str>, title: Option<&str>) -> Self {
+        let mut resolved = Self::default();
+
+        for rule in rules.iter().filter(|rule| rule.matches(app_id, title)) {
+            let WindowRule {
+                app_id: _,
+                title: _,
+                workspace,
+                output,
+                floating,
+                size,
+                opacity,
+                fullscreen,
+                focus,
+            } = rule.clone();
+
+            resolved.workspace = workspace.or(resolved.workspace);
+            resolved.output = output.or(resolved.output);
+            resolved.floating = floating.or(resolved.floating);
+            resolved.size = size.or(resolved.size);
+            resolved.opacity = opacity.or(resolved.opacity);
+            resolved.fullscreen = fullscreen.or(resolved.fullscreen);
+            resolved.focus = focus.or(resolved.focus);
+        }
+
+        resolved
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.0.as_str(), f)
+    }
+}
+
+impl TryFrom<MatchConfig> for Match {
+    type Error = regex::Error;
+
+    fn try_from(config: MatchConfig) -> Result<Self, Self::Error> {
+        match config {
+            MatchConfig::Exact(value) => Ok(Match::Exact(value)),
+            MatchConfig::Regex { regex } => {
+                regex::Regex::new(&regex).map(|r| Match::Regex(Regex(r)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> Vec<WindowRule> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Rules {
+            window_rules: Vec<WindowRule>,
+        }
+        toml::from_str::<Rules>(toml).unwrap().window_rules
+    }
+
+    #[test]
+    fn exact_matches_need_the_whole_string() {
+        let [rule] = &rules("[[window-rules]]\napp-id = \"foot\"")[..] else {
+            unreachable!();
+        };
+        assert!(rule.matches(Some("foot"), None));
+        assert!(!rule.matches(Some("footclient"), None));
+        assert!(!rule.matches(Some("Foot"), None));
+        assert!(!rule.matches(None, None));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_unless_anchored() {
+        let [loose, anchored] = &rules(
+            r#"
+            [[window-rules]]
+            title = { regex = "Mozilla" }
+            [[window-rules]]
+            title = { regex = "^Mozilla" }
+            "#,
+        )[..] else {
+            unreachable!();
+        };
+        assert!(loose.matches(None, Some("Home - Mozilla Firefox")));
+        assert!(!anchored.matches(None, Some("Home - Mozilla Firefox")));
+        assert!(anchored.matches(None, Some("Mozilla Firefox")));
+        assert!(!loose.matches(None, None));
+    }
+
+    #[test]
+    fn rules_need_every_matcher_they_have() {
+        let [rule, any] = &rules(
+            r#"
+            [[window-rules]]
+            app-id = "firefox"
+            title = { regex = "Picture-in-Picture" }
+            [[window-rules]]
+            floating = true
+            "#,
+        )[..] else {
+            unreachable!();
+        };
+        assert!(rule.matches(Some("firefox"), Some("Picture-in-Picture")));
+        assert!(!rule.matches(Some("firefox"), Some("Home")));
+        assert!(!rule.matches(Some("foot"), Some("Picture-in-Picture")));
+        assert!(any.matches(None, None));
+    }
+
+    #[test]
+    fn later_rules_override_earlier_ones() {
+        let rules = rules(
+            r#"
+            [[window-rules]]
+            floating = true
+            opacity = 0.9
+            workspace = 2
+            [[window-rules]]
+            app-id = "mpv"
+            floating = false
+            focus = false
+            [[window-rules]]
+            app-id = "foot"
+            workspace = "web"
+            "#,
+        );
+
+        assert_eq!(
+            ResolvedRules::new(&rules, Some("mpv"), None),
+            ResolvedRules {
+                workspace: Some(WorkspaceReference::Index(2)),
+                floating: Some(false),
+                opacity: Some(0.9),
+                focus: Some(false),
+                ..ResolvedRules::default()
+            }
+        );
+        assert_eq!(
+            ResolvedRules::new(&rules, Some("foot"), None),
+            ResolvedRules {
+                workspace: Some(WorkspaceReference::Name("web".to_owned())),
+                floating: Some(true),
+                opacity: Some(0.9),
+                ..ResolvedRules::default()
+            }
+        );
+    }
+
+    #[test]
+    fn no_matching_rules_resolve_to_nothing() {
+        let rules = rules("[[window-rules]]\napp-id = \"mpv\"\nfloating = true");
+        assert_eq!(
+            ResolvedRules::new(&rules, Some("foot"), None),
+            ResolvedRules::default()
+        );
+    }
+}
@@ -1,32 +1,66 @@
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
 use std::time::Duration;
 
-use smithay::backend::renderer::element::solid::{SolidColorBuffer, SolidColorRenderElement};
-use smithay::backend::renderer::element::Kind;
-use smithay::backend::renderer::ImportAll;
-use smithay::desktop::space::{space_render_elements, SpaceRenderElements};
-use smithay::desktop::{PopupManager, Space, Window, WindowSurfaceType};
+use anyhow::Context;
+use smithay::backend::renderer::element::memory::MemoryRenderBufferRenderElement;
+use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
+use smithay::backend::renderer::element::AsRenderElements;
+use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::backend::renderer::{ImportAll, ImportMem};
+use smithay::desktop::space::{SpaceElement, SpaceRenderElements};
+use smithay::desktop::utils::send_frames_surface_tree;
+use smithay::desktop::{
+    layer_map_for_output, LayerSurface, PopupManager, Space, Window, WindowSurfaceType,
+};
+use smithay::input::keyboard::Keycode;
+use smithay::input::pointer::{CursorImageStatus, MotionEvent};
 use smithay::input::{Seat, SeatState};
 use smithay::output::Output;
 use smithay::reexports::calloop::generic::Generic;
-use smithay::reexports::calloop::{Interest, LoopHandle, LoopSignal, Mode, PostAction};
-use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::WmCapabilities;
-use smithay::reexports::wayland_server::backend::{ClientData, ClientId, DisconnectReason};
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::calloop::{
+    Interest, LoopHandle, LoopSignal, Mode, PostAction, RegistrationToken,
+};
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::{
+    self, WmCapabilities,
+};
+use smithay::reexports::wayland_server::backend::{
+    ClientData, ClientId, DisconnectReason, GlobalId,
+};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{Display, DisplayHandle};
 use smithay::render_elements;
-use smithay::utils::{Logical, Point};
+use smithay::utils::{Logical, Point, Rectangle, Scale, Size, SERIAL_COUNTER};
 use smithay::wayland::compositor::{CompositorClientState, CompositorState};
+use smithay::wayland::cursor_shape::CursorShapeManagerState;
+use smithay::wayland::keyboard_shortcuts_inhibit::{
+    KeyboardShortcutsInhibitState, KeyboardShortcutsInhibitor,
+};
 use smithay::wayland::output::OutputManagerState;
 use smithay::wayland::selection::data_device::DataDeviceState;
+use smithay::wayland::shell::wlr_layer::{KeyboardInteractivity, Layer, WlrLayerShellState};
+use smithay::wayland::shell::xdg::decoration::XdgDecorationState;
 use smithay::wayland::shell::xdg::XdgShellState;
 use smithay::wayland::shm::ShmState;
 use smithay::wayland::socket::ListeningSocketSource;
+use smithay::wayland::xdg_activation::XdgActivationState;
 
+use crate::backend::headless::Headless;
 use crate::backend::tty::Tty;
 use crate::backend::winit::Winit;
-use crate::backend::Backend;
+use crate::backend::{Backend, BackendKind};
+use crate::binds::Action;
+use crate::config::{self, Config};
+use crate::cursor::{Cursor, CursorRenderElements};
+use crate::decoration::{self, DecorationRenderElements, Tab, TabBars};
+use crate::grabs::resize_grab::ResizeEdge;
+use crate::ipc::server::IpcServer;
+use crate::ipc::Event;
+use crate::layout::{self, Direction, LayoutKind, LayoutWindow, WindowHints};
+use crate::window::WindowExt;
+use crate::workspace::Workspace;
 use crate::LoopData;
 
 pub struct Twm {
@@ -35,26 +69,55 @@ pub struct Twm {
     pub stop_signal: LoopSignal,
     pub display_handle: DisplayHandle,
 
+    pub config: Config,
+
+    /// Holds the outputs and the windows of the workspaces shown on them, which are the only
+    /// ones rendered.
     pub space: Space<Window>,
+    pub workspaces: Vec<Workspace>,
+    pub output_state: HashMap<Output, OutputState>,
+    /// The output that has focus, workspace actions apply to the workspace shown on it.
+    pub active_output: Option<Output>,
 
     // Smithay State
     pub compositor_state: CompositorState,
     pub xdg_shell_state: XdgShellState,
+    pub xdg_decoration_state: XdgDecorationState,
+    pub activation_state: XdgActivationState,
+    pub layer_shell_state: WlrLayerShellState,
     pub shm_state: ShmState,
     pub output_manager_state: OutputManagerState,
     pub seat_state: SeatState<State>,
     pub data_device_state: DataDeviceState,
+    pub cursor_shape_manager_state: CursorShapeManagerState,
+    pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
     pub popups: PopupManager,
 
     pub seat: Seat<State>,
-    pub output: Option<Output>,
+    pub ipc_server: Option<IpcServer>,
+    pub keyboard_shortcuts_inhibiting_surfaces: HashMap<WlSurface, KeyboardShortcutsInhibitor>,
+    /// Keys that triggered a binding, with the action to run on release if any.
+    pub suppressed_keys: HashMap<Keycode, Option<Action>>,
+    pub key_repeat: Option<(Keycode, RegistrationToken)>,
 
-    pub pointer_buffer: SolidColorBuffer,
+    pub cursor: Cursor,
+    /// Set while a redraw for the next frame of an animated cursor is scheduled.
+    pub cursor_animation_queued: bool,
+    /// Set while a relayout is scheduled, see [`Twm::queue_refresh_geometry`].
+    pub refresh_geometry_queued: bool,
+}
 
+pub struct OutputState {
+    pub global: GlobalId,
+    /// Index of the workspace shown on this output.
+    pub workspace: usize,
     // Set to `true` if there's a redraw queued on the event loop. Reset to `false` in redraw()
     // which means that you cannot queue more than one redraw at once.
     pub redraw_queued: bool,
     pub waiting_for_vblank: bool,
+    /// Set when a redraw is asked for while waiting for the vblank, which then queues it.
+    pub redraw_pending: bool,
+    pub tab_bars: TabBars,
 }
 
 pub struct State {
@@ -67,20 +130,192 @@ impl State {
         event_loop: LoopHandle<'static, LoopData>,
         stop_signal: LoopSignal,
         display: Display<State>,
-    ) -> Self {
+        config: Config,
+        backend: Option<BackendKind>,
+        socket_name: Option<String>,
+    ) -> anyhow::Result<Self> {
         let has_display =
             env::var_os("WAYLAND_DISPLAY").is_some() || env::var_os("DISPLAY").is_some();
+        let backend = match backend {
+            Some(backend) => backend,
+            None if has_display => BackendKind::Winit,
+            // The tty backend would be next, once it is implemented.
+            None => anyhow::bail!(
+                "no Wayland or X11 display to run in, pass `--backend headless` to run without one"
+            ),
+        };
 
-        let mut backend = if has_display {
-            Backend::Winit(Winit::new(event_loop.clone()))
-        } else {
-            Backend::Tty(Tty::new(event_loop.clone()))
+        let mut backend = match backend {
+            BackendKind::Winit => Backend::Winit(Winit::new(event_loop.clone())),
+            BackendKind::Tty => Backend::Tty(Tty::new(event_loop.clone())?),
+            BackendKind::Headless => Backend::Headless(Headless::new(event_loop.clone())?),
         };
 
-        let mut twm = Twm::new(event_loop, stop_signal, display, &backend);
+        let mut twm = Twm::new(
+            event_loop,
+            stop_signal,
+            display,
+            config,
+            &backend,
+            socket_name.as_deref(),
+        )?;
         backend.init(&mut twm);
 
-        Self { backend, twm }
+        Ok(Self { backend, twm })
+    }
+
+    /// Shows the workspace at `idx` on the active output.
+    ///
+    /// A workspace that is already shown on another output is not moved, that output gets focus
+    /// instead.
+    pub fn switch_workspace(&mut self, idx: usize) {
+        let Some(output) = self.twm.active_output.clone() else {
+            return;
+        };
+        if idx == self.twm.active_workspace_idx() || idx >= self.twm.workspaces.len() {
+            return;
+        }
+
+        if let Some(other) = self.twm.output_for_workspace(idx).cloned() {
+            self.focus_output(&other);
+            return;
+        }
+
+        let old = self.twm.active_workspace_idx();
+        for window in &self.twm.workspaces[old].windows {
+            self.twm.space.unmap_elem(window);
+        }
+
+        self.twm.output_state.get_mut(&output).unwrap().workspace = idx;
+        debug!(
+            "switched to workspace {} on {}",
+            self.twm.active_workspace().name,
+            output.name()
+        );
+        self.twm.refresh_geometry();
+        self.focus_active_window();
+
+        self.twm.queue_redraw(&output);
+    }
+
+    /// Moves the focused window to the workspace at `idx`, switching to it if `follow` is set.
+    pub fn move_to_workspace(&mut self, idx: usize, follow: bool) {
+        if idx == self.twm.active_workspace_idx() || idx >= self.twm.workspaces.len() {
+            return;
+        }
+
+        let Some(window) = self.twm.active_workspace().focus.clone() else {
+            return;
+        };
+
+        self.twm.remove_window(&window);
+        self.twm.workspaces[idx].add_window(window, true);
+
+        if follow {
+            self.switch_workspace(idx);
+        } else {
+            self.twm.queue_refresh_geometry();
+            self.focus_active_window();
+        }
+    }
+
+    /// Moves the focused window to the workspace shown on the output in `direction`, following it
+    /// there.
+    pub fn move_to_output(&mut self, direction: Direction) {
+        let Some(output) = self.twm.output_in_direction(direction) else {
+            return;
+        };
+        let idx = self.twm.output_state[output].workspace;
+        self.move_to_workspace(idx, true);
+    }
+
+    /// Makes `output` the active output, warping the pointer to its center and focusing the
+    /// focused window of its workspace.
+    pub fn focus_output(&mut self, output: &Output) {
+        let Some(geometry) = self.twm.space.output_geometry(output) else {
+            return;
+        };
+        self.twm.active_output = Some(output.clone());
+
+        self.warp_pointer(geometry.loc.to_f64() + geometry.size.to_f64().downscale(2.).to_point());
+        self.focus_active_window();
+
+        self.twm.queue_redraw(output);
+    }
+
+    /// Moves the pointer to `location`, in global coordinates.
+    pub fn warp_pointer(&mut self, location: Point<f64, Logical>) {
+        let under = self.twm.surface_under(location);
+        let pointer = self.twm.seat.get_pointer().unwrap();
+        let old_location = pointer.current_location();
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time: self.twm.start_time.elapsed().as_millis() as u32,
+            },
+        );
+        pointer.frame(self);
+
+        self.twm.queue_redraw_under(old_location);
+        self.twm.queue_redraw_under(location);
+    }
+
+    /// Moves the pointer to the center of the focused window of the active workspace if the config
+    /// asks for it. Called when the focus moved without the pointer.
+    pub fn warp_pointer_to_focus(&mut self) {
+        if !self.twm.config.focus.warp_pointer {
+            return;
+        }
+        let Some(window) = self.twm.active_workspace().focus.clone() else {
+            return;
+        };
+        let Some(geometry) = self.twm.space.element_geometry(&window) else {
+            return;
+        };
+
+        let location = self.twm.seat.get_pointer().unwrap().current_location();
+        if !geometry.to_f64().contains(location) {
+            self.warp_pointer(
+                geometry.loc.to_f64() + geometry.size.to_f64().downscale(2.).to_point(),
+            );
+        }
+    }
+
+    /// Gives keyboard focus to `window`, which has to be on a shown workspace.
+    ///
+    /// Nothing changes while a layer surface holds the focus exclusively. The window is raised if
+    /// the config asks for it.
+    pub fn focus_window(&mut self, window: &Window) {
+        if self.twm.exclusive_layer_surface().is_some() {
+            return;
+        }
+        if self.twm.config.focus.raise {
+            self.twm.space.raise_element(window, true);
+            self.twm.raise_floating();
+        }
+
+        let surface = window.toplevel().unwrap().wl_surface().clone();
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, Some(surface), SERIAL_COUNTER.next_serial());
+    }
+
+    /// Gives keyboard focus to the focused window of the active workspace, if any.
+    ///
+    /// A layer surface with exclusive keyboard interactivity keeps the focus instead for as long as
+    /// it is mapped.
+    pub fn focus_active_window(&mut self) {
+        let focus = self.twm.exclusive_layer_surface().or_else(|| {
+            self.twm
+                .active_workspace()
+                .focus
+                .as_ref()
+                .map(|window| window.toplevel().unwrap().wl_surface().clone())
+        });
+        let keyboard = self.twm.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
     }
 }
 
@@ -89,8 +324,10 @@ impl Twm {
         event_loop: LoopHandle<'static, LoopData>,
         stop_signal: LoopSignal,
         display: Display<State>,
+        config: Config,
         backend: &Backend,
-    ) -> Self {
+        socket_name: Option<&str>,
+    ) -> anyhow::Result<Self> {
         let start_time = std::time::Instant::now();
 
         let display_handle = display.handle();
@@ -104,11 +341,17 @@ impl Twm {
                 WmCapabilities::WindowMenu,
             ],
         );
+        let xdg_decoration_state = XdgDecorationState::new::<State>(&display_handle);
+        let activation_state = XdgActivationState::new::<State>(&display_handle);
+        let layer_shell_state = WlrLayerShellState::new::<State>(&display_handle);
         let shm_state = ShmState::new::<State>(&display_handle, vec![]);
         let output_manager_state =
             OutputManagerState::new_with_xdg_output::<State>(&display_handle);
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<State>(&display_handle);
+        let cursor_shape_manager_state = CursorShapeManagerState::new::<State>(&display_handle);
+        let keyboard_shortcuts_inhibit_state =
+            KeyboardShortcutsInhibitState::new::<State>(&display_handle);
         let popups = PopupManager::default();
 
         // A seat is a group of keyboards, pointer and touch devices.
@@ -118,7 +361,12 @@ impl Twm {
         // Notify clients that we have a keyboard, for the sake of the example we assume that
         // keyboard is always present. You may want to track keyboard hot-plug in real
         // compositor.
-        seat.add_keyboard(Default::default(), 200, 25).unwrap();
+        seat.add_keyboard(
+            Default::default(),
+            config.input.repeat_delay,
+            config.input.repeat_rate,
+        )
+        .unwrap();
 
         // Notify clients that we have a pointer (mouse)
         // Here we assume that there is always pointer plugged in
@@ -130,9 +378,13 @@ impl Twm {
         // Outputs become views of a part of the Space and can be rendered via Space::render_output.
         let space = Space::default();
 
-        // Creates a new listening socket, automatically choosing the next available `wayland`
-        // socket name.
-        let listening_socket = ListeningSocketSource::new_auto().unwrap();
+        // Creates a new listening socket, with the requested name or automatically choosing the
+        // next available `wayland` socket name.
+        let listening_socket = match socket_name {
+            Some(name) => ListeningSocketSource::with_name(name)
+                .with_context(|| format!("error binding the Wayland socket {name}"))?,
+            None => ListeningSocketSource::new_auto().context("error binding a Wayland socket")?,
+        };
 
         // Get the name of the listening socket.
         // Clients will connect to this socket.
@@ -157,6 +409,14 @@ impl Twm {
             socket_name.to_string_lossy()
         );
 
+        let ipc_server = match IpcServer::start(&event_loop, &socket_name.to_string_lossy()) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                warn!("error starting the IPC server: {err:?}");
+                None
+            }
+        };
+
         // You also need to add the display itself to the event loop, so that client events will be
         // processed by wayland-server.
         event_loop
@@ -172,31 +432,113 @@ impl Twm {
             )
             .unwrap();
 
-        let pointer_buffer = SolidColorBuffer::new((16, 16), [1., 0.8, 0., 1.]);
+        let cursor = Cursor::new(config.appearance.pointer_color);
+        let workspaces = config
+            .workspaces
+            .iter()
+            .map(|name| Workspace::new(name.clone(), config.layout.default.build(&config.layout)))
+            .collect();
 
-        Self {
+        Ok(Self {
             start_time,
             stop_signal,
             event_loop,
             display_handle,
 
+            config,
+
             space,
+            workspaces,
+            output_state: HashMap::new(),
+            active_output: None,
 
             compositor_state,
             xdg_shell_state,
+            xdg_decoration_state,
+            activation_state,
+            layer_shell_state,
             shm_state,
             output_manager_state,
             seat_state,
             data_device_state,
+            cursor_shape_manager_state,
+            keyboard_shortcuts_inhibit_state,
             popups,
 
             seat,
-            output: None,
+            ipc_server,
+            keyboard_shortcuts_inhibiting_surfaces: HashMap::new(),
+            suppressed_keys: HashMap::new(),
+            key_repeat: None,
 
-            pointer_buffer,
+            cursor,
+            cursor_animation_queued: false,
+            refresh_geometry_queued: false,
+        })
+    }
 
-            redraw_queued: false,
-            waiting_for_vblank: false,
+    /// Starts managing `output`, placing it to the right of the existing outputs and showing the
+    /// first workspace that isn't shown anywhere else on it.
+    pub fn add_output(&mut self, output: Output) {
+        let global = output.create_global::<State>(&self.display_handle);
+
+        let x = self
+            .space
+            .outputs()
+            .filter_map(|output| self.space.output_geometry(output))
+            .map(|geometry| geometry.loc.x + geometry.size.w)
+            .max()
+            .unwrap_or(0);
+        output.change_current_state(None, None, None, Some((x, 0).into()));
+        self.space.map_output(&output, (x, 0));
+
+        let workspace = match (0..self.workspaces.len())
+            .find(|idx| self.output_for_workspace(*idx).is_none())
+        {
+            Some(idx) => idx,
+            None => {
+                // More outputs than configured workspaces, make up a new one.
+                let name = (self.workspaces.len() + 1).to_string();
+                let layout = self.config.layout.default.build(&self.config.layout);
+                self.workspaces.push(Workspace::new(name, layout));
+                self.workspaces.len() - 1
+            }
+        };
+        info!(
+            "added output {} showing workspace {}",
+            output.name(),
+            self.workspaces[workspace].name
+        );
+
+        self.output_state.insert(
+            output.clone(),
+            OutputState {
+                global,
+                workspace,
+                redraw_queued: false,
+                waiting_for_vblank: false,
+                redraw_pending: false,
+                tab_bars: TabBars::default(),
+            },
+        );
+        if self.active_output.is_none() {
+            self.active_output = Some(output.clone());
+        }
+
+        self.queue_refresh_geometry();
+
+        if let Some(info) = self.ipc_output(&output) {
+            self.send_ipc_event(Event::OutputAdded(info));
+        }
+    }
+
+    /// Called by the backends after they changed the mode, scale or transform of `output`.
+    pub fn output_resized(&mut self, output: &Output) {
+        layer_map_for_output(output).arrange();
+        self.queue_refresh_geometry();
+
+        if let Some(info) = self.ipc_output(output) {
+            self.send_ipc_event(Event::OutputResized(info));
         }
     }
 
@@ -204,129 +546,872 @@ impl Twm {
         &self,
         pos: Point<f64, Logical>,
     ) -> Option<(WlSurface, Point<f64, Logical>)> {
+        if let Some((layer, location)) = self.layer_under(pos) {
+            return layer
+                .surface_under(pos - location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(s, p)| (s, (p + location).to_f64()));
+        }
+
+        self.window_under(pos).and_then(|(window, location)| {
+            window
+                .surface_under(pos - location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(s, p)| (s, (p + location).to_f64()))
+        })
+    }
+
+    /// The window at `pos` and the location of its surface, like [`Space::element_under`] but only
+    /// counting the workspace shown on the output there: windows scrolled off their output can
+    /// reach into a neighbouring one, which doesn't draw them.
+    pub fn window_under(&self, pos: Point<f64, Logical>) -> Option<(&Window, Point<i32, Logical>)> {
+        let output = self.space.output_under(pos).next()?;
+        let ws = &self.workspaces[self.output_state[output].workspace];
+
         self.space
-            .element_under(pos)
-            .and_then(|(window, location)| {
+            .elements()
+            .rev()
+            .filter(|window| ws.contains(window))
+            .find_map(|window| {
+                let location = self.space.element_location(window)? - window.geometry().loc;
                 window
-                    .surface_under(pos - location.to_f64(), WindowSurfaceType::ALL)
-                    .map(|(s, p)| (s, (p + location).to_f64()))
+                    .is_in_input_region(&(pos - location.to_f64()))
+                    .then_some((window, location))
+            })
+    }
+
+    /// The window at `pos` like [`Twm::window_under`], but counting its borders and title bar as
+    /// well.
+    pub fn frame_under(&self, pos: Point<f64, Logical>) -> Option<&Window> {
+        let output = self.space.output_under(pos).next()?;
+        let ws = &self.workspaces[self.output_state[output].workspace];
+
+        self.space
+            .elements()
+            .rev()
+            .filter(|window| ws.contains(window))
+            .find(|window| {
+                let Some(geometry) = self.space.element_geometry(window) else {
+                    return false;
+                };
+                // Fullscreen and maximized windows are drawn without decorations.
+                let frame = if window.is_fullscreen() || window.is_maximized() {
+                    geometry
+                } else {
+                    decoration::frame_geometry(window, geometry, &self.config.decorations)
+                };
+                let location = geometry.loc - window.geometry().loc;
+                frame.to_f64().contains(pos)
+                    || window.is_in_input_region(&(pos - location.to_f64()))
+            })
+    }
+
+    /// The window of the tab at `pos`, unless a floating window covers it.
+    pub fn tab_under(&self, pos: Point<f64, Logical>) -> Option<&Window> {
+        if self.window_under(pos).is_some() {
+            return None;
+        }
+
+        let output = self.space.output_under(pos).next()?;
+        let ws = &self.workspaces[self.output_state[output].workspace];
+        let gap = self.config.layout.gap;
+
+        let (bar, geometry) = ws.layout.tab_bars().into_iter().find_map(|bar| {
+            let geometry = layout::shrink_tile(bar.geometry, gap);
+            geometry.to_f64().contains(pos).then_some((bar, geometry))
+        })?;
+
+        let count = bar.tabs.len() as f64;
+        let fraction = if bar.stacked {
+            (pos.y - f64::from(geometry.loc.y)) / f64::from(geometry.size.h)
+        } else {
+            (pos.x - f64::from(geometry.loc.x)) / f64::from(geometry.size.w)
+        };
+        let id = bar.tabs.get((fraction * count) as usize)?;
+        ws.windows.iter().find(|window| window.id() == *id)
+    }
+
+    /// The tiled window whose tile borders on the gap at `pos`, along with the edges of it the gap
+    /// runs along.
+    pub fn tile_edge_under(&self, pos: Point<f64, Logical>) -> Option<(Window, ResizeEdge)> {
+        let output = self.space.output_under(pos).next()?;
+        let ws = &self.workspaces[self.output_state[output].workspace];
+        let gap = self.config.layout.gap;
+
+        let frames: Vec<_> = ws
+            .tiled_windows()
+            .filter(|window| ws.layout.is_visible(window.id()))
+            .filter_map(|window| {
+                let geometry = self.space.element_geometry(window)?;
+                let frame = decoration::frame_geometry(window, geometry, &self.config.decorations);
+                Some((window, frame.to_f64()))
             })
+            .collect();
+        if frames.iter().any(|(_, frame)| frame.contains(pos)) {
+            return None;
+        }
+
+        frames.into_iter().find_map(|(window, frame)| {
+            let reach = Rectangle::from_loc_and_size(
+                frame.loc - Point::from((f64::from(gap), f64::from(gap))),
+                frame.size + Size::from((f64::from(gap * 2), f64::from(gap * 2))),
+            );
+            if !reach.contains(pos) {
+                return None;
+            }
+
+            let mut edges = ResizeEdge::empty();
+            if pos.x < frame.loc.x {
+                edges |= ResizeEdge::LEFT;
+            } else if pos.x >= frame.loc.x + frame.size.w {
+                edges |= ResizeEdge::RIGHT;
+            }
+            if pos.y < frame.loc.y {
+                edges |= ResizeEdge::TOP;
+            } else if pos.y >= frame.loc.y + frame.size.h {
+                edges |= ResizeEdge::BOTTOM;
+            }
+            Some((window.clone(), edges))
+        })
+    }
+
+    /// The layer surface that gets the input at `pos` and its location: one on the overlay or top
+    /// layer, or one on the lower layers if there is no window there.
+    pub fn layer_under(
+        &self,
+        pos: Point<f64, Logical>,
+    ) -> Option<(LayerSurface, Point<i32, Logical>)> {
+        let output = self.space.output_under(pos).next()?;
+        let output_loc = self.space.output_geometry(output)?.loc;
+        let map = layer_map_for_output(output);
+
+        let window_under = self.window_under(pos).is_some();
+        let layers: &[Layer] = if window_under {
+            &[Layer::Overlay, Layer::Top]
+        } else {
+            &[Layer::Overlay, Layer::Top, Layer::Bottom, Layer::Background]
+        };
+
+        layers.iter().find_map(|layer| {
+            let surface = map.layer_under(*layer, pos - output_loc.to_f64())?;
+            let location = map.layer_geometry(surface).unwrap().loc + output_loc;
+            Some((surface.clone(), location))
+        })
     }
 
-    pub fn queue_redraw(&mut self) {
-        if self.redraw_queued || self.waiting_for_vblank {
+    pub fn queue_redraw(&mut self, output: &Output) {
+        let Some(state) = self.output_state.get_mut(output) else {
+            return;
+        };
+        if state.redraw_queued {
+            return;
+        }
+        if state.waiting_for_vblank {
+            state.redraw_pending = true;
             return;
         }
 
-        self.redraw_queued = true;
+        state.redraw_queued = true;
 
-        self.event_loop.insert_idle(|data| {
-            data.state.twm.redraw(&mut data.state.backend);
+        let output = output.clone();
+        self.event_loop.insert_idle(move |data| {
+            data.state.twm.redraw(&mut data.state.backend, &output);
         });
     }
 
-    pub fn redraw(&mut self, backend: &mut Backend) {
+    pub fn queue_redraw_all(&mut self) {
+        let outputs: Vec<_> = self.output_state.keys().cloned().collect();
+        for output in &outputs {
+            self.queue_redraw(output);
+        }
+    }
+
+    /// Queues a redraw of the outputs containing `pos`.
+    pub fn queue_redraw_under(&mut self, pos: Point<f64, Logical>) {
+        let outputs: Vec<_> = self.space.output_under(pos).cloned().collect();
+        for output in &outputs {
+            self.queue_redraw(output);
+        }
+    }
+
+    pub fn redraw(&mut self, backend: &mut Backend, output: &Output) {
         let _span = tracy_client::span!("redraw");
 
-        assert!(self.redraw_queued);
-        assert!(!self.waiting_for_vblank);
-        self.redraw_queued = false;
+        // The output may have gone away since the redraw was queued.
+        let Some(state) = self.output_state.get_mut(output) else {
+            return;
+        };
+        assert!(state.redraw_queued);
+        assert!(!state.waiting_for_vblank);
+        state.redraw_queued = false;
 
-        let elements = space_render_elements(
-            backend.renderer(),
-            [&self.space],
-            self.output.as_ref().unwrap(),
-            1.,
-        )
-        .unwrap();
+        let output_geometry = self.space.output_geometry(output).unwrap();
+        let renderer = backend.renderer();
+
+        let scale = output.current_scale().fractional_scale();
+        let pointer_location = self.seat.get_pointer().unwrap().current_location();
+
+        // Elements are ordered front to back: the pointer, the overlay and top layers, the
+        // windows with their decorations, then the bottom and background layers.
+        let mut elements: Vec<_> = self
+            .cursor
+            .render_elements(
+                renderer,
+                pointer_location - output_geometry.loc.to_f64(),
+                scale,
+                self.start_time.elapsed(),
+            )
+            .into_iter()
+            .map(OutputRenderElements::Pointer)
+            .collect();
+
+        let layer_elements = |renderer: &mut GlesRenderer, layer| {
+            layer_render_elements(renderer, output, layer)
+                .into_iter()
+                .map(|element| OutputRenderElements::from(SpaceRenderElements::Surface(element)))
+        };
+        elements.extend(layer_elements(renderer, Layer::Overlay));
+        elements.extend(layer_elements(renderer, Layer::Top));
+
+        // Windows scrolled off their output into this one are left out.
+        let ws = &self.workspaces[self.output_state[output].workspace];
+        let focus = self.seat.get_keyboard().unwrap().current_focus();
+        for window in self
+            .space
+            .elements_for_output(output)
+            .rev()
+            .filter(|window| ws.contains(window))
+        {
+            // Locations in the space are those of the window geometry, which may not start at the
+            // origin of the surface because of client-side shadows.
+            let location = self.space.element_location(window).unwrap() - output_geometry.loc;
+            let render_location =
+                (location - window.geometry().loc).to_physical_precise_round(scale);
+            elements.extend(
+                window
+                    .render_elements::<WaylandSurfaceRenderElement<_>>(
+                        renderer,
+                        render_location,
+                        Scale::from(scale),
+                        window.opacity(),
+                    )
+                    .into_iter()
+                    .map(|element| {
+                        OutputRenderElements::from(SpaceRenderElements::Surface(element))
+                    }),
+            );
 
-        let mut elements: Vec<_> = elements
+            // Those cover their whole output or usable area, leaving no room for decorations.
+            if window.is_fullscreen() || window.is_maximized() {
+                continue;
+            }
+            let focused = focus.as_ref() == Some(window.toplevel().unwrap().wl_surface());
+            let color = decoration::color(&self.config.decorations, focused, window.is_urgent());
+            let geometry = Rectangle::from_loc_and_size(location, window.geometry().size);
+            elements.extend(
+                window
+                    .decoration()
+                    .render_elements(
+                        renderer,
+                        window,
+                        geometry,
+                        color,
+                        scale,
+                        &self.config.decorations,
+                    )
+                    .into_iter()
+                    .map(OutputRenderElements::Decoration),
+            );
+        }
+
+        // Tab bars don't overlap tiled windows, so only floating ones can cover them.
+        let config = &self.config.decorations;
+        let bars = ws
+            .layout
+            .tab_bars()
             .into_iter()
-            .map(OutputRenderElements::from)
+            .map(|bar| {
+                let geometry = layout::shrink_tile(bar.geometry, self.config.layout.gap);
+                let geometry =
+                    Rectangle::from_loc_and_size(geometry.loc - output_geometry.loc, geometry.size);
+                let tabs = bar
+                    .tabs
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, id)| {
+                        let window = ws.windows.iter().find(|window| window.id() == *id);
+                        let focused = window.is_some_and(|window| {
+                            focus.as_ref() == Some(window.toplevel().unwrap().wl_surface())
+                        });
+                        let urgent = window.is_some_and(Window::is_urgent);
+                        Tab {
+                            title: window.and_then(Window::title).unwrap_or_default(),
+                            color: decoration::tab_color(
+                                config,
+                                focused,
+                                idx == bar.active,
+                                urgent,
+                            ),
+                        }
+                    })
+                    .collect();
+                (geometry, tabs, bar.stacked)
+            })
             .collect();
-        elements.insert(
-            0,
-            OutputRenderElements::Pointer(SolidColorRenderElement::from_buffer(
-                &self.pointer_buffer,
-                self.seat
-                    .get_pointer()
-                    .unwrap()
-                    .current_location()
-                    .to_physical_precise_round(1.),
-                1.,
-                1.,
-                Kind::Unspecified,
-            )),
+        let tab_bars = &mut self.output_state.get_mut(output).unwrap().tab_bars;
+        elements.extend(
+            tab_bars
+                .render_elements(renderer, bars, scale, config)
+                .into_iter()
+                .map(OutputRenderElements::TabBar),
         );
 
-        backend.render(self, &elements);
+        elements.extend(layer_elements(renderer, Layer::Bottom));
+        elements.extend(layer_elements(renderer, Layer::Background));
 
-        let output = self.output.as_ref().unwrap();
-        self.space.elements().for_each(|window| {
+        backend.render(self, output, &elements);
+
+        if output_geometry.to_f64().contains(pointer_location) {
+            if let Some(delay) = self.cursor.next_frame_in(scale, self.start_time.elapsed()) {
+                self.queue_cursor_animation(delay);
+            }
+        }
+    }
+
+    /// Redraws the output under the pointer after `delay`, when an animated cursor moves on to its
+    /// next frame.
+    fn queue_cursor_animation(&mut self, delay: Duration) {
+        if self.cursor_animation_queued {
+            return;
+        }
+        self.cursor_animation_queued = true;
+
+        self.event_loop
+            .insert_source(Timer::from_duration(delay), |_, _, data| {
+                let twm = &mut data.state.twm;
+                twm.cursor_animation_queued = false;
+                let location = twm.seat.get_pointer().unwrap().current_location();
+                twm.queue_redraw_under(location);
+                TimeoutAction::Drop
+            })
+            .unwrap();
+    }
+
+    /// Sends frame callbacks to the windows on `output`. Backends call this once a frame has been
+    /// presented.
+    pub fn send_frame_callbacks(&self, output: &Output) {
+        // Windows scrolled off their output into this one aren't drawn here.
+        let ws = &self.workspaces[self.output_state[output].workspace];
+        let windows = self.space.elements_for_output(output);
+        for window in windows.filter(|window| ws.contains(window)) {
             window.send_frame(
                 output,
                 self.start_time.elapsed(),
                 Some(Duration::ZERO),
                 |_, _| Some(output.clone()),
-            )
+            );
+        }
+
+        for layer in layer_map_for_output(output).layers() {
+            layer.send_frame(
+                output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(output.clone()),
+            );
+        }
+
+        if let CursorImageStatus::Surface(surface) = self.cursor.status() {
+            send_frames_surface_tree(
+                surface,
+                output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(output.clone()),
+            );
+        }
+    }
+
+    /// Takes back the outputs [`Space::refresh`] told windows they entered by reaching into them
+    /// from their own, since only the output showing their workspace draws them. Windows whose
+    /// workspace moves onto such an output are told they entered it after all.
+    pub fn refresh_window_outputs(&self) {
+        for window in self.windows() {
+            let mut foreign = window.foreign_outputs();
+            // Unmapping a window has it leave every output.
+            let Some(bbox) = self.space.element_bbox(window) else {
+                foreign.clear();
+                continue;
+            };
+
+            for (output, state) in &self.output_state {
+                let Some(output_geometry) = self.space.output_geometry(output) else {
+                    continue;
+                };
+                let overlap = output_geometry.intersection(bbox);
+                let shown = self.workspaces[state.workspace].contains(window);
+                let left = foreign.contains(output);
+
+                match (overlap, shown, left) {
+                    (Some(_), false, false) => {
+                        window.output_leave(output);
+                        foreign.push(output.clone());
+                    }
+                    (Some(mut overlap), true, true) => {
+                        // The overlap is relative to the window, like the space gives it.
+                        overlap.loc -= bbox.loc;
+                        window.output_enter(output, overlap);
+                        foreign.retain(|o| o != output);
+                    }
+                    // The space told it it left already.
+                    (None, _, true) => foreign.retain(|o| o != output),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// The part of `output` left to windows once the exclusive zones of its layer surfaces are
+    /// taken out, in global coordinates.
+    pub fn usable_area(&self, output: &Output) -> Rectangle<i32, Logical> {
+        let mut area = layer_map_for_output(output).non_exclusive_zone();
+        area.loc += self.space.output_geometry(output).unwrap().loc;
+        area
+    }
+
+    /// The output whose layer map holds the layer surface `surface`.
+    pub fn output_for_layer(&self, surface: &WlSurface) -> Option<Output> {
+        self.space
+            .outputs()
+            .find(|output| {
+                layer_map_for_output(output)
+                    .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+            .cloned()
+    }
+
+    /// A layer surface on the top or overlay layer that asked for exclusive keyboard focus, if
+    /// any is mapped.
+    pub fn exclusive_layer_surface(&self) -> Option<WlSurface> {
+        self.space.outputs().find_map(|output| {
+            let map = layer_map_for_output(output);
+            let layer = [Layer::Overlay, Layer::Top]
+                .into_iter()
+                .flat_map(|layer| map.layers_on(layer))
+                .find(|layer| {
+                    layer.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive
+                })?;
+            Some(layer.wl_surface().clone())
+        })
+    }
+
+    /// Index of the workspace shown on the active output.
+    pub fn active_workspace_idx(&self) -> usize {
+        self.active_output
+            .as_ref()
+            .and_then(|output| self.output_state.get(output))
+            .map_or(0, |state| state.workspace)
+    }
+
+    pub fn active_workspace(&self) -> &Workspace {
+        &self.workspaces[self.active_workspace_idx()]
+    }
+
+    pub fn active_workspace_mut(&mut self) -> &mut Workspace {
+        let idx = self.active_workspace_idx();
+        &mut self.workspaces[idx]
+    }
+
+    /// The output showing the workspace at `idx`, if it is visible.
+    pub fn output_for_workspace(&self, idx: usize) -> Option<&Output> {
+        self.output_state
+            .iter()
+            .find(|(_, state)| state.workspace == idx)
+            .map(|(output, _)| output)
+    }
+
+    /// The closest output in `direction` from the active output, comparing their centers.
+    pub fn output_in_direction(&self, direction: Direction) -> Option<&Output> {
+        let active = self.active_output.as_ref()?;
+        let from = self.space.output_geometry(active)?;
+
+        let candidates = self
+            .space
+            .outputs()
+            .filter(|output| *output != active)
+            .filter_map(|output| Some((output, self.space.output_geometry(output)?)));
+        direction.closest(from, candidates)
+    }
+
+    /// The closest window in `direction` from the focused window of the active workspace,
+    /// comparing their centers. Windows on every shown workspace are candidates.
+    ///
+    /// The layout has the first say, so that windows behind other tabs can be reached.
+    pub fn window_in_direction(&self, direction: Direction) -> Option<&Window> {
+        let ws = self.active_workspace();
+        let focus = ws.focus.as_ref()?;
+        let neighbour = ws.layout.focus_neighbour(focus.id(), direction);
+        if let Some(window) = neighbour.and_then(|id| ws.windows.iter().find(|w| w.id() == id)) {
+            return Some(window);
+        }
+
+        let from = self.space.element_geometry(focus)?;
+
+        let candidates = self
+            .space
+            .elements()
+            .filter(|window| *window != focus)
+            .filter_map(|window| Some((window, self.space.element_geometry(window)?)));
+        direction.closest(from, candidates)
+    }
+
+    /// Every window on every workspace.
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.workspaces.iter().flat_map(|ws| &ws.windows)
+    }
+
+    /// Finds the window whose toplevel surface is `surface`, on any workspace.
+    pub fn find_window(&self, surface: &WlSurface) -> Option<&Window> {
+        self.windows()
+            .find(|w| w.toplevel().unwrap().wl_surface() == surface)
+    }
+
+    pub fn remove_window(&mut self, window: &Window) {
+        for ws in &mut self.workspaces {
+            ws.remove_window(window);
+        }
+        self.space.unmap_elem(window);
+    }
+
+    pub fn set_layout(&mut self, kind: LayoutKind) {
+        info!("switching to the {kind} layout");
+        let layout = kind.build(&self.config.layout);
+        self.active_workspace_mut().set_layout(layout);
+        self.queue_refresh_geometry();
+    }
+
+    /// Brings the workspace names in line with the config. Workspaces are never dropped here since
+    /// they may still hold windows.
+    fn sync_workspaces(&mut self) {
+        for (idx, name) in self.config.workspaces.iter().enumerate() {
+            match self.workspaces.get_mut(idx) {
+                Some(ws) => ws.name.clone_from(name),
+                None => {
+                    let layout = self.config.layout.default.build(&self.config.layout);
+                    self.workspaces.push(Workspace::new(name.clone(), layout));
+                }
+            }
+        }
+    }
+
+    pub fn reload_config(&mut self, config: Config) {
+        if self.config == config {
+            return;
+        }
+
+        info!("applying the new config");
+
+        if self.config.input != config.input {
+            self.seat
+                .get_keyboard()
+                .unwrap()
+                .change_repeat_info(config.input.repeat_rate, config.input.repeat_delay);
+        }
+
+        if self.config.appearance.pointer_color != config.appearance.pointer_color {
+            self.cursor
+                .set_fallback_color(config.appearance.pointer_color);
+        }
+
+        let layout_changed = self.config.layout != config.layout;
+        let decorations_changed = self.config.decorations != config.decorations;
+        self.config = config;
+        self.sync_workspaces();
+
+        if layout_changed {
+            // The layouts keep their trees and columns, only their parameters change.
+            for ws in &mut self.workspaces {
+                ws.layout.apply_config(&self.config.layout);
+            }
+        }
+        if layout_changed || decorations_changed {
+            self.queue_refresh_geometry();
+        }
+
+        self.queue_redraw_all();
+        self.send_ipc_event(Event::ConfigReloaded);
+    }
+
+    /// Arranges the workspaces on the next iteration of the event loop, so that everything that
+    /// changed until then is handled by a single relayout.
+    pub fn queue_refresh_geometry(&mut self) {
+        if self.refresh_geometry_queued {
+            return;
+        }
+        self.refresh_geometry_queued = true;
+
+        self.event_loop.insert_idle(|data| {
+            let twm = &mut data.state.twm;
+            twm.refresh_geometry_queued = false;
+            twm.refresh_geometry();
+            twm.queue_redraw_all();
         });
     }
 
+    /// Arranges the workspace shown on every output within that output's area, then sends every
+    /// window whose state changed a single configure.
+    ///
+    /// Floating windows are only mapped where they were left, and stay above the tiled ones.
+    /// Fullscreen and maximized windows cover their output or its usable area instead of their
+    /// place in the layout.
     pub fn refresh_geometry(&mut self) {
-        let space = &mut self.space;
+        let gap = self.config.layout.gap;
+        let decorations = &self.config.decorations;
+        let mut laid_out = Vec::new();
+        let mut tabs_changed = false;
+        let tile_hints = |window: &Window| tile_hints(window, gap, decorations);
 
-        let output = space.outputs().next().cloned().unwrap();
+        for (output, state) in &self.output_state {
+            let output_geometry = self.space.output_geometry(output).unwrap();
+            let usable_area = self.usable_area(output);
+            let area = layout::with_gaps(usable_area, gap);
 
-        let output_geometry = space.output_geometry(&output).unwrap();
-        let output_width = output_geometry.size.w;
-        let output_height = output_geometry.size.h;
+            let ws = &mut self.workspaces[state.workspace];
+            for window in ws.windows.iter().filter(|w| !w.map_state().is_laid_out()) {
+                self.space.unmap_elem(window);
+            }
 
-        let gap = 6;
+            // Windows too large for the whole area are placed like floating ones until there is
+            // room for them again, they would overlap the others anyway.
+            let (tiled, oversized): (Vec<_>, Vec<_>) = ws
+                .tiled_windows()
+                .cloned()
+                .partition(|window| tile_hints(window).fits(area.size));
+            let floating: Vec<_> = ws.floating_windows().cloned().chain(oversized).collect();
+            let layout_windows: Vec<_> = tiled
+                .iter()
+                .map(|window| LayoutWindow {
+                    hints: tile_hints(window),
+                    ..window.layout_window()
+                })
+                .collect();
+            let geometries = ws.layout.arrange(area, &layout_windows);
 
-        let elements_count = space.elements().count() as i32;
+            for (window, geometry) in tiled.iter().zip(geometries) {
+                let tabbed = ws.layout.has_tab(window.id());
+                tabs_changed |= window.is_tabbed() != tabbed;
+                window.set_tabbed(tabbed);
 
-        let mut resizes = vec![];
+                // Windows stay within their own hints, centered in tiles that don't suit them.
+                let geometry = pinned_geometry(window, output_geometry, usable_area)
+                    .unwrap_or_else(|| {
+                        let tile = layout::shrink_tile(geometry, gap);
+                        let area = decoration::window_area(window, tile, decorations);
+                        window.layout_window().hints.fit(area)
+                    });
 
-        for (i, window) in space.elements().enumerate() {
-            let (mut x, mut y) = (gap, gap);
-            let (mut width, mut height) = (output_width - gap * 2, output_height - gap * 2);
+                window.toplevel().unwrap().with_pending_state(|s| {
+                    s.size = Some(geometry.size);
+                });
 
-            if elements_count > 1 {
-                width -= gap;
-                width /= 2;
+                // Windows behind other tabs or scrolled out of view keep their size for when they
+                // come back.
+                if ws.layout.is_visible(window.id()) {
+                    self.space.map_element(window.clone(), geometry.loc, false);
+                } else {
+                    self.space.unmap_elem(window);
+                }
             }
 
-            if i > 0 {
-                height /= elements_count - 1;
+            for window in &floating {
+                tabs_changed |= window.is_tabbed();
+                window.set_tabbed(false);
 
-                x += width + gap;
-                y += height * (i as i32 - 1);
-            }
+                if let Some(geometry) = pinned_geometry(window, output_geometry, usable_area) {
+                    window.toplevel().unwrap().with_pending_state(|s| {
+                        s.size = Some(geometry.size);
+                    });
+                    self.space.map_element(window.clone(), geometry.loc, false);
+                    continue;
+                }
 
-            if i > 1 {
-                height -= gap;
-                y += gap;
+                let geometry = match window.floating_geometry() {
+                    Some(geometry) => geometry,
+                    None => {
+                        // New floating windows get centered once the client picked a size.
+                        let size = window.geometry().size;
+                        if size.w <= 0 || size.h <= 0 {
+                            continue;
+                        }
+                        let loc = usable_area.loc - output_geometry.loc
+                            + Point::from((
+                                (usable_area.size.w - size.w) / 2,
+                                (usable_area.size.h - size.h) / 2,
+                            ));
+                        let geometry = Rectangle::from_loc_and_size(loc, size);
+                        window.set_floating_geometry(geometry);
+                        geometry
+                    }
+                };
+
+                self.space
+                    .map_element(window.clone(), output_geometry.loc + geometry.loc, false);
             }
 
-            resizes.push((window.clone(), (width, height), (x, y)));
+            laid_out.extend(tiled);
+            laid_out.extend(floating);
+        }
+
+        self.raise_floating();
+
+        // Configures only go out for actual changes, so windows that kept their size hear nothing.
+        for window in laid_out {
+            window.toplevel().unwrap().send_pending_configure();
         }
 
-        for (window, dimensions, positon) in resizes {
-            window.toplevel().unwrap().with_pending_state(|s| {
-                s.size = Some(dimensions.into());
-            });
+        // Title bars that came or went with a tab change the hints the layouts went by.
+        if tabs_changed {
+            self.queue_refresh_geometry();
+        }
+    }
+
+    /// Drags the `edge` side of the tile of `window` to `to`, moving the split of the layout it
+    /// borders on, and relays the windows out if that changed anything.
+    pub fn move_tile_edge(&mut self, window: &Window, edge: Direction, to: i32) {
+        let Some(idx) = self.workspaces.iter().position(|ws| ws.contains(window)) else {
+            return;
+        };
+        let Some(output) = self.output_for_workspace(idx) else {
+            return;
+        };
+        let gap = self.config.layout.gap;
+        let area = layout::with_gaps(self.usable_area(output), gap);
+
+        let decorations = &self.config.decorations;
+        let ws = &mut self.workspaces[idx];
+        let layout_windows: Vec<_> = ws
+            .tiled_windows()
+            .filter(|window| tile_hints(window, gap, decorations).fits(area.size))
+            .map(|window| LayoutWindow {
+                hints: tile_hints(window, gap, decorations),
+                ..window.layout_window()
+            })
+            .collect();
+        if ws
+            .layout
+            .move_edge(area, &layout_windows, window.id(), edge, to)
+        {
+            self.queue_refresh_geometry();
+        }
+    }
+
+    /// Puts the floating windows back above the tiled ones, keeping the order within each group.
+    pub fn raise_floating(&mut self) {
+        let floating: Vec<_> = self
+            .space
+            .elements()
+            .filter(|window| window.is_floating())
+            .cloned()
+            .collect();
+        for window in &floating {
+            self.space.raise_element(window, false);
+        }
+    }
+
+    /// Remembers where a floating window is, after it was moved or resized.
+    pub fn save_floating_geometry(&self, window: &Window) {
+        let Some(geometry) = self.space.element_geometry(window) else {
+            return;
+        };
+        let Some(idx) = self.workspaces.iter().position(|ws| ws.contains(window)) else {
+            return;
+        };
+        let Some(output) = self.output_for_workspace(idx) else {
+            return;
+        };
 
-            window.toplevel().unwrap().send_configure();
+        let output_loc = self.space.output_geometry(output).unwrap().loc;
+        window.set_floating_geometry(Rectangle::from_loc_and_size(
+            geometry.loc - output_loc,
+            geometry.size,
+        ));
+    }
 
-            space.map_element(window, positon, false);
+    /// Switches `window` between tiling and floating.
+    ///
+    /// A window floating again gets back the geometry it had, one floating for the first time
+    /// keeps its size and gets centered.
+    pub fn toggle_floating(&mut self, window: &Window) {
+        if window.is_floating() {
+            self.save_floating_geometry(window);
+            window.set_floating(false);
+        } else {
+            window.set_floating(true);
+            if let Some(geometry) = window.floating_geometry() {
+                let toplevel = window.toplevel().unwrap();
+                toplevel.with_pending_state(|state| state.size = Some(geometry.size));
+            }
         }
+
+        self.update_decoration_mode(window);
+        self.queue_refresh_geometry();
     }
 }
 
+/// The hints of the tile of `window`. The layouts deal in tiles, which hold the gaps and
+/// decorations on top of the windows.
+fn tile_hints(window: &Window, gap: i32, decorations: &config::Decorations) -> WindowHints {
+    let frame = decoration::frame_size(window, decorations) + Size::from((gap, gap));
+    window.layout_window().hints.grow(frame)
+}
+
+/// The geometry of a fullscreen or maximized window: its whole output, or the usable area of it.
+fn pinned_geometry(
+    window: &Window,
+    output_geometry: Rectangle<i32, Logical>,
+    usable_area: Rectangle<i32, Logical>,
+) -> Option<Rectangle<i32, Logical>> {
+    let states = window
+        .toplevel()
+        .unwrap()
+        .with_pending_state(|state| state.states.clone());
+
+    if states.contains(xdg_toplevel::State::Fullscreen) {
+        Some(output_geometry)
+    } else if states.contains(xdg_toplevel::State::Maximized) {
+        Some(usable_area)
+    } else {
+        None
+    }
+}
+
+/// Renders the surfaces on `layer` of `output`, front to back.
+fn layer_render_elements(
+    renderer: &mut GlesRenderer,
+    output: &Output,
+    layer: Layer,
+) -> Vec<WaylandSurfaceRenderElement<GlesRenderer>> {
+    let map = layer_map_for_output(output);
+    let scale = Scale::from(output.current_scale().fractional_scale());
+
+    map.layers_on(layer)
+        .rev()
+        .flat_map(|surface| {
+            let location = map
+                .layer_geometry(surface)
+                .unwrap()
+                .loc
+                .to_physical_precise_round(scale);
+            surface.render_elements(renderer, location, scale, 1.)
+        })
+        .collect()
+}
+
 render_elements! {
-    pub OutputRenderElements<R, E> where R: ImportAll;
+    pub OutputRenderElements<R, E> where R: ImportAll + ImportMem;
     Space=SpaceRenderElements<R, E>,
-    Pointer = SolidColorRenderElement,
+    Decoration = DecorationRenderElements<R>,
+    TabBar = MemoryRenderBufferRenderElement<R>,
+    Pointer = CursorRenderElements<R>,
 }
 
 #[derive(Default)]
@@ -0,0 +1,368 @@
+use std::cell::{Cell, RefCell, RefMut};
+
+use smithay::desktop::Window;
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
+use smithay::utils::{Logical, Rectangle};
+use smithay::wayland::compositor;
+use smithay::wayland::shell::xdg::{
+    SurfaceCachedState, XdgToplevelSurfaceData, XdgToplevelSurfaceRoleAttributes,
+};
+
+use crate::decoration::Decoration;
+use crate::layout::{LayoutWindow, WindowHints, WindowId};
+use crate::rules::ResolvedRules;
+
+/// Where a window is in its life cycle, as far as the layout is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapState {
+    /// Waiting for its initial commit.
+    New,
+    /// Placed and laid out, but without a buffer yet.
+    Configured,
+    /// Showing a buffer.
+    Mapped,
+    /// The client took its buffer away, the window is left out until it commits again.
+    Unmapped,
+}
+
+impl MapState {
+    /// Whether the window gets a place in the layout.
+    pub fn is_laid_out(self) -> bool {
+        matches!(self, MapState::Configured | MapState::Mapped)
+    }
+}
+
+/// Helpers for the per-window state twm keeps on top of smithay's [`Window`].
+pub trait WindowExt {
+    /// The stable id of this window, assigned the first time it is asked for.
+    fn id(&self) -> WindowId;
+
+    /// Describes this window to a [`Layout`](crate::layout::Layout).
+    fn layout_window(&self) -> LayoutWindow;
+
+    fn app_id(&self) -> Option<String>;
+
+    fn title(&self) -> Option<String>;
+
+    /// Whether the title changed since the last call. Windows start out without a title.
+    fn title_changed(&self) -> bool;
+
+    /// Whether the client acked a fullscreen configure.
+    fn is_fullscreen(&self) -> bool;
+
+    /// Whether the client acked a maximized configure.
+    fn is_maximized(&self) -> bool;
+
+    /// Whether we told the client that we draw its decorations.
+    fn is_server_decorated(&self) -> bool;
+
+    /// The decoration mode the client asked for, if it has a preference.
+    fn requested_decoration_mode(&self) -> Option<DecorationMode>;
+
+    fn set_requested_decoration_mode(&self, mode: Option<DecorationMode>);
+
+    /// Whether the window sits right under a tab, which shows its title in place of a title bar.
+    fn is_tabbed(&self) -> bool;
+
+    fn set_tabbed(&self, tabbed: bool);
+
+    /// Whether the window asked for attention since it last had focus.
+    fn is_urgent(&self) -> bool;
+
+    fn set_urgent(&self, urgent: bool);
+
+    /// The borders and title bar drawn around this window.
+    fn decoration(&self) -> RefMut<'_, Decoration>;
+
+    /// Outputs the window reaches into without being drawn there, which it was told it left.
+    fn foreign_outputs(&self) -> RefMut<'_, Vec<Output>>;
+
+    /// Whether the window is left out of tiling and keeps its own geometry.
+    fn is_floating(&self) -> bool;
+
+    fn set_floating(&self, floating: bool);
+
+    /// Where the window goes while floating, relative to the output of its workspace. Unset until
+    /// the window is first placed.
+    fn floating_geometry(&self) -> Option<Rectangle<i32, Logical>>;
+
+    fn set_floating_geometry(&self, geometry: Rectangle<i32, Logical>);
+
+    /// Whether the window should start out floating: dialogs, and windows that can't be resized.
+    fn prefers_floating(&self) -> bool;
+
+    /// Whether the client got a reply to its initial commit.
+    fn is_initial_configure_sent(&self) -> bool;
+
+    fn map_state(&self) -> MapState;
+
+    /// Moves the window along its life cycle. Unmapping it makes the next commit an initial one
+    /// again, which xdg-shell expects a new configure for.
+    fn set_map_state(&self, state: MapState);
+
+    /// The window rules that matched the last time they were evaluated.
+    fn rules(&self) -> ResolvedRules;
+
+    fn set_rules(&self, rules: ResolvedRules);
+
+    /// Opacity the window is drawn with.
+    fn opacity(&self) -> f32;
+}
+
+impl WindowExt for Window {
+    fn id(&self) -> WindowId {
+        self.user_data().insert_if_missing(WindowId::next);
+        *self.user_data().get::<WindowId>().unwrap()
+    }
+
+    fn layout_window(&self) -> LayoutWindow {
+        let (min_size, max_size) =
+            compositor::with_states(self.toplevel().unwrap().wl_surface(), |states| {
+                let mut guard = states.cached_state.get::<SurfaceCachedState>();
+                let data = guard.current();
+                (data.min_size, data.max_size)
+            });
+
+        LayoutWindow {
+            id: self.id(),
+            hints: WindowHints { min_size, max_size },
+        }
+    }
+
+    fn app_id(&self) -> Option<String> {
+        with_toplevel_data(self, |data| data.app_id.clone())
+    }
+
+    fn title(&self) -> Option<String> {
+        with_toplevel_data(self, |data| data.title.clone())
+    }
+
+    fn title_changed(&self) -> bool {
+        let title = self.title();
+        self.user_data()
+            .insert_if_missing(|| LastTitle(RefCell::new(None)));
+        let mut last = self.user_data().get::<LastTitle>().unwrap().0.borrow_mut();
+
+        if *last == title {
+            return false;
+        }
+        *last = title;
+        true
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.toplevel()
+            .unwrap()
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Fullscreen)
+    }
+
+    fn is_maximized(&self) -> bool {
+        self.toplevel()
+            .unwrap()
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Maximized)
+    }
+
+    fn is_server_decorated(&self) -> bool {
+        // The pending state, since windows are laid out for the mode before the client acks it.
+        self.toplevel()
+            .unwrap()
+            .with_pending_state(|state| state.decoration_mode)
+            == Some(DecorationMode::ServerSide)
+    }
+
+    fn requested_decoration_mode(&self) -> Option<DecorationMode> {
+        self.user_data()
+            .get::<RequestedDecorationMode>()
+            .and_then(|requested| requested.0.get())
+    }
+
+    fn set_requested_decoration_mode(&self, mode: Option<DecorationMode>) {
+        self.user_data()
+            .insert_if_missing(|| RequestedDecorationMode(Cell::new(None)));
+        self.user_data()
+            .get::<RequestedDecorationMode>()
+            .unwrap()
+            .0
+            .set(mode);
+    }
+
+    fn is_tabbed(&self) -> bool {
+        self.user_data()
+            .get::<Tabbed>()
+            .is_some_and(|tabbed| tabbed.0.get())
+    }
+
+    fn set_tabbed(&self, tabbed: bool) {
+        self.user_data()
+            .insert_if_missing(|| Tabbed(Cell::new(false)));
+        self.user_data().get::<Tabbed>().unwrap().0.set(tabbed);
+    }
+
+    fn is_urgent(&self) -> bool {
+        self.user_data()
+            .get::<Urgent>()
+            .is_some_and(|urgent| urgent.0.get())
+    }
+
+    fn set_urgent(&self, urgent: bool) {
+        self.user_data()
+            .insert_if_missing(|| Urgent(Cell::new(false)));
+        self.user_data().get::<Urgent>().unwrap().0.set(urgent);
+    }
+
+    fn decoration(&self) -> RefMut<'_, Decoration> {
+        self.user_data()
+            .insert_if_missing(|| RefCell::new(Decoration::default()));
+        self.user_data()
+            .get::<RefCell<Decoration>>()
+            .unwrap()
+            .borrow_mut()
+    }
+
+    fn foreign_outputs(&self) -> RefMut<'_, Vec<Output>> {
+        self.user_data()
+            .insert_if_missing(|| ForeignOutputs(RefCell::new(Vec::new())));
+        self.user_data()
+            .get::<ForeignOutputs>()
+            .unwrap()
+            .0
+            .borrow_mut()
+    }
+
+    fn is_floating(&self) -> bool {
+        self.user_data()
+            .get::<Floating>()
+            .is_some_and(|floating| floating.floating.get())
+    }
+
+    fn set_floating(&self, floating: bool) {
+        self.user_data().insert_if_missing(Floating::default);
+        self.user_data()
+            .get::<Floating>()
+            .unwrap()
+            .floating
+            .set(floating);
+    }
+
+    fn floating_geometry(&self) -> Option<Rectangle<i32, Logical>> {
+        self.user_data()
+            .get::<Floating>()
+            .and_then(|floating| floating.geometry.get())
+    }
+
+    fn set_floating_geometry(&self, geometry: Rectangle<i32, Logical>) {
+        self.user_data().insert_if_missing(Floating::default);
+        self.user_data()
+            .get::<Floating>()
+            .unwrap()
+            .geometry
+            .set(Some(geometry));
+    }
+
+    fn prefers_floating(&self) -> bool {
+        if self.toplevel().unwrap().parent().is_some() {
+            return true;
+        }
+
+        let hints = self.layout_window().hints;
+        hints.min_size.w > 0 && hints.min_size.h > 0 && hints.min_size == hints.max_size
+    }
+
+    fn is_initial_configure_sent(&self) -> bool {
+        compositor::with_states(self.toplevel().unwrap().wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        })
+    }
+
+    fn map_state(&self) -> MapState {
+        self.user_data()
+            .get::<Cell<MapState>>()
+            .map_or(MapState::New, Cell::get)
+    }
+
+    fn set_map_state(&self, state: MapState) {
+        self.user_data()
+            .insert_if_missing(|| Cell::new(MapState::New));
+        self.user_data().get::<Cell<MapState>>().unwrap().set(state);
+
+        if state == MapState::Unmapped {
+            compositor::with_states(self.toplevel().unwrap().wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .initial_configure_sent = false;
+            });
+        }
+    }
+
+    fn rules(&self) -> ResolvedRules {
+        self.user_data()
+            .get::<RefCell<ResolvedRules>>()
+            .map(|rules| rules.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    fn set_rules(&self, rules: ResolvedRules) {
+        self.user_data()
+            .insert_if_missing(|| RefCell::new(ResolvedRules::default()));
+        *self
+            .user_data()
+            .get::<RefCell<ResolvedRules>>()
+            .unwrap()
+            .borrow_mut() = rules;
+    }
+
+    fn opacity(&self) -> f32 {
+        self.user_data()
+            .get::<RefCell<ResolvedRules>>()
+            .and_then(|rules| rules.borrow().opacity)
+            .map_or(1., |opacity| opacity.clamp(0., 1.))
+    }
+}
+
+/// The title as of the last [`WindowExt::title_changed`] call.
+struct LastTitle(RefCell<Option<String>>);
+
+struct RequestedDecorationMode(Cell<Option<DecorationMode>>);
+
+struct Tabbed(Cell<bool>);
+
+struct ForeignOutputs(RefCell<Vec<Output>>);
+
+struct Urgent(Cell<bool>);
+
+#[derive(Default)]
+struct Floating {
+    floating: Cell<bool>,
+    geometry: Cell<Option<Rectangle<i32, Logical>>>,
+}
+
+fn with_toplevel_data<T>(
+    window: &Window,
+    f: impl FnOnce(&XdgToplevelSurfaceRoleAttributes) -> T,
+) -> T {
+    compositor::with_states(window.toplevel().unwrap().wl_surface(), |states| {
+        let data = states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap();
+        f(&data)
+    })
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use smithay::desktop::Window;
+
+use crate::layout::Layout;
+use crate::window::WindowExt;
+
+/// A set of windows arranged by its own layout. Only the active workspace is mapped into the
+/// `Space`.
+pub struct Workspace {
+    pub name: String,
+    /// Every window on the workspace, in layout order. Floating ones are left out of the layout.
+    pub windows: Vec<Window>,
+    pub focus: Option<Window>,
+    /// Windows that had the focus before `focus`, most recent last.
+    pub focus_history: Vec<Window>,
+    pub layout: Box<dyn Layout>,
+}
+
+/// Refers to a workspace either by its 1-based number or by its name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WorkspaceReference {
+    Index(usize),
+    Name(String),
+}
+
+impl Workspace {
+    pub fn new(name: String, layout: Box<dyn Layout>) -> Self {
+        Self {
+            name,
+            windows: Vec::new(),
+            focus: None,
+            focus_history: Vec::new(),
+            layout,
+        }
+    }
+
+    pub fn contains(&self, window: &Window) -> bool {
+        self.windows.contains(window)
+    }
+
+    /// The windows arranged by the layout, in layout order.
+    pub fn tiled_windows(&self) -> impl Iterator<Item = &Window> {
+        self.laid_out_windows()
+            .filter(|window| !window.is_floating())
+    }
+
+    /// The windows that keep their own geometry.
+    pub fn floating_windows(&self) -> impl Iterator<Item = &Window> {
+        self.laid_out_windows()
+            .filter(|window| window.is_floating())
+    }
+
+    /// The windows that are shown, leaving out those that wait for their initial commit or were
+    /// unmapped by their client.
+    pub fn laid_out_windows(&self) -> impl Iterator<Item = &Window> {
+        self.windows
+            .iter()
+            .filter(|window| window.map_state().is_laid_out())
+    }
+
+    /// Adds a window at the end of the layout order, focusing it if `focus` is set.
+    pub fn add_window(&mut self, window: Window, focus: bool) {
+        self.windows.push(window.clone());
+        if focus {
+            self.set_focus(Some(window));
+        }
+    }
+
+    pub fn remove_window(&mut self, window: &Window) -> bool {
+        let Some(idx) = self.windows.iter().position(|w| w == window) else {
+            return false;
+        };
+
+        self.windows.remove(idx);
+        self.focus_history.retain(|w| w != window);
+        self.unfocus(window);
+
+        true
+    }
+
+    /// Gives the focus back to the window focused before `window`, if `window` has it. Windows
+    /// their client unmapped are passed over, and stay in the history for when they come back.
+    pub fn unfocus(&mut self, window: &Window) {
+        if self.focus.as_ref() == Some(window) {
+            let idx = self
+                .focus_history
+                .iter()
+                .rposition(|w| w.map_state().is_laid_out());
+            self.focus = idx.map(|idx| self.focus_history.remove(idx));
+            self.layout.set_focus(self.focus.as_ref().map(Window::id));
+        }
+    }
+
+    /// Replaces the layout, telling the new one which window has the focus.
+    pub fn set_layout(&mut self, mut layout: Box<dyn Layout>) {
+        layout.set_focus(self.focus.as_ref().map(Window::id));
+        self.layout = layout;
+    }
+
+    /// Focuses `window`, pushing the previously focused window onto the history.
+    pub fn set_focus(&mut self, window: Option<Window>) {
+        if self.focus == window {
+            return;
+        }
+        self.layout.set_focus(window.as_ref().map(Window::id));
+
+        if let Some(old) = self.focus.take() {
+            self.focus_history.retain(|w| *w != old);
+            self.focus_history.push(old);
+        }
+        if let Some(window) = &window {
+            self.focus_history.retain(|w| w != window);
+        }
+        self.focus = window;
+    }
+
+    /// The window that had the focus before the focused one.
+    pub fn last_focused(&self) -> Option<&Window> {
+        self.focus_history.last()
+    }
+}
+
+impl WorkspaceReference {
+    /// Index of the referenced workspace in `workspaces`.
+    pub fn find(&self, workspaces: &[Workspace]) -> Option<usize> {
+        match self {
+            WorkspaceReference::Index(number) => {
+                number.checked_sub(1).filter(|idx| *idx < workspaces.len())
+            }
+            WorkspaceReference::Name(name) => workspaces.iter().position(|ws| ws.name == *name),
+        }
+    }
+}